@@ -0,0 +1,261 @@
+use serde_json::Value;
+use std::fmt;
+use std::sync::Arc;
+use tracing::{info, warn};
+
+use crate::deepseek::{
+  ChatCompletionResponse, ChatMessage, DeepSeekClient, StreamEvent, Tool, ToolCall,
+};
+use crate::mcp_client::Progress;
+use crate::mcp_host::McpHost;
+use crate::mcp_schema::InvalidArguments;
+
+pub const DEFAULT_MAX_ITERATIONS: usize = 10;
+
+pub const DEFAULT_SYSTEM_PROMPT: &str = "你是一个专业的助手，可以：\n1. 提供天气信息和穿衣建议\n2. 搜索互联网获取实时信息\n请根据用户的问题，选择合适的工具来提供帮助。\n\n这是一个基于Model Context Protocol (MCP)的工具系统。";
+
+#[derive(Debug)]
+pub enum AgentError {
+  // The model kept requesting tools until the iteration cap was reached
+  MaxIterationsExceeded(usize),
+  // The chat completion response did not contain a message
+  EmptyResponse(ChatCompletionResponse),
+}
+
+impl fmt::Display for AgentError {
+  fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+    match self {
+      AgentError::MaxIterationsExceeded(max) => write!(
+        f,
+        "Agent stopped after {} iterations without a final answer",
+        max
+      ),
+      AgentError::EmptyResponse(response) => {
+        write!(f, "DeepSeek response {} contained no message", response.id)
+      }
+    }
+  }
+}
+
+impl std::error::Error for AgentError {}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct AgentReply {
+  pub answer: String,
+  // reasoning_content of every turn, only present with deepseek-reasoner
+  pub reasoning: Option<String>,
+}
+
+// Multi-turn agent: keeps the whole conversation, executes every tool call the
+// model makes through the MCP host and re-queries DeepSeek until it answers without tools.
+pub struct Agent {
+  deepseek: DeepSeekClient,
+  mcp_host: McpHost,
+  tools: Vec<Tool>,
+  messages: Vec<ChatMessage>,
+  max_iterations: usize,
+  log_reasoning: bool,
+  on_tool_progress: Option<ToolProgressHandler>,
+}
+
+// Called with the exposed tool name and every progress update of a running tool call
+pub type ToolProgressHandler = Arc<dyn Fn(&str, &Progress) + Send + Sync>;
+
+impl Agent {
+  // Build an agent over the tools of every server connected to the host
+  pub fn new(deepseek: DeepSeekClient, mcp_host: McpHost) -> Self {
+    let tools = mcp_host.list_tools().iter().map(Tool::from_mcp).collect();
+
+    Self {
+      deepseek,
+      mcp_host,
+      tools,
+      messages: vec![ChatMessage::system(DEFAULT_SYSTEM_PROMPT)],
+      max_iterations: DEFAULT_MAX_ITERATIONS,
+      log_reasoning: false,
+      on_tool_progress: None,
+    }
+  }
+
+  pub fn with_system_prompt(mut self, system_prompt: &str) -> Self {
+    self.messages[0] = ChatMessage::system(system_prompt);
+    self
+  }
+
+  pub fn with_max_iterations(mut self, max_iterations: usize) -> Self {
+    self.max_iterations = max_iterations;
+    self
+  }
+
+  // Log each turn's reasoning_content at info level
+  pub fn with_reasoning_log(mut self, log_reasoning: bool) -> Self {
+    self.log_reasoning = log_reasoning;
+    self
+  }
+
+  // Request progress updates for tool calls and pass them to `on_progress`
+  pub fn with_tool_progress(
+    mut self,
+    on_progress: impl Fn(&str, &Progress) + Send + Sync + 'static,
+  ) -> Self {
+    self.on_tool_progress = Some(Arc::new(on_progress));
+    self
+  }
+
+  // Full conversation history, including assistant tool_calls and tool results.
+  // reasoning_content is never stored here since it must not be replayed.
+  pub fn messages(&self) -> &[ChatMessage] {
+    &self.messages
+  }
+
+  // Ask a question and loop until the model produces a final answer
+  pub async fn run(&mut self, user_query: &str) -> Result<AgentReply, Box<dyn std::error::Error>> {
+    self.run_loop(user_query, None).await
+  }
+
+  // Like `run`, but streams every turn and hands Content/Reasoning fragments to
+  // `on_delta` as they arrive. Tool calls are only executed once fully assembled.
+  pub async fn run_streaming(
+    &mut self,
+    user_query: &str,
+    on_delta: &mut dyn FnMut(&StreamEvent),
+  ) -> Result<AgentReply, Box<dyn std::error::Error>> {
+    self.run_loop(user_query, Some(on_delta)).await
+  }
+
+  async fn run_loop(
+    &mut self,
+    user_query: &str,
+    mut on_delta: Option<&mut dyn FnMut(&StreamEvent)>,
+  ) -> Result<AgentReply, Box<dyn std::error::Error>> {
+    self.messages.push(ChatMessage::user(user_query));
+    let mut reasoning = Vec::new();
+
+    for iteration in 1..=self.max_iterations {
+      self.refresh_tools().await;
+      let request = self
+        .deepseek
+        .request(self.messages.clone())
+        .with_tools(self.tools.clone());
+      let message = match on_delta.as_mut() {
+        Some(on_delta) => {
+          let stream = self.deepseek.chat_stream(&request).await?;
+          stream.collect(|delta| on_delta(delta)).await?.message
+        }
+        None => {
+          let response = self.deepseek.chat(&request).await?;
+          match response.message().cloned() {
+            Some(message) => message,
+            None => return Err(AgentError::EmptyResponse(response).into()),
+          }
+        }
+      };
+      if let Some(turn_reasoning) = message.reasoning_content.as_deref() {
+        if self.log_reasoning {
+          info!("Agent: Reasoning (iteration {}): {}", iteration, turn_reasoning);
+        }
+        reasoning.push(turn_reasoning.to_string());
+      }
+      self.messages.push(message.without_reasoning());
+
+      let tool_calls = message.tool_calls();
+      if tool_calls.is_empty() {
+        info!("Agent: Final answer after {} iteration(s)", iteration);
+        return Ok(AgentReply {
+          answer: message.content.unwrap_or_default(),
+          reasoning: (!reasoning.is_empty()).then(|| reasoning.join("\n\n")),
+        });
+      }
+
+      info!(
+        "Agent: Iteration {} requested {} tool call(s)",
+        iteration,
+        tool_calls.len()
+      );
+      for call in tool_calls {
+        let content = self.execute_tool_call(call).await;
+        self.messages.push(ChatMessage::tool(&call.id, content));
+      }
+    }
+
+    Err(AgentError::MaxIterationsExceeded(self.max_iterations).into())
+  }
+
+  // Pick up tools added or removed on a server since the last turn. On
+  // failure the previous tool list stays in use and the next turn tries again.
+  async fn refresh_tools(&mut self) {
+    match self.mcp_host.refresh_tools_if_changed().await {
+      Ok(true) => {
+        self.tools = self
+          .mcp_host
+          .list_tools()
+          .iter()
+          .map(Tool::from_mcp)
+          .collect();
+        info!("Agent: Now offering {} tool(s)", self.tools.len());
+      }
+      Ok(false) => {}
+      Err(e) => warn!("Agent: Could not refresh tools: {}", e),
+    }
+  }
+
+  // Tool failures are reported back to the model instead of aborting the loop,
+  // so it can retry with different arguments or answer without the tool.
+  async fn execute_tool_call(&self, call: &ToolCall) -> String {
+    let tool_name = &call.function.name;
+    let arguments = &call.function.arguments;
+    info!("Agent: Calling tool {} with {}", tool_name, arguments);
+
+    let arguments: Value = match serde_json::from_str(arguments) {
+      Ok(arguments) => arguments,
+      Err(e) => return format!("Invalid tool arguments: {}", e),
+    };
+
+    let result = match &self.on_tool_progress {
+      Some(on_progress) => {
+        let on_progress = on_progress.clone();
+        let tool = tool_name.clone();
+        self
+          .mcp_host
+          .call_tool_with_progress(tool_name, arguments, move |progress| {
+            on_progress(&tool, &progress)
+          })
+          .await
+      }
+      None => self.mcp_host.call_tool(tool_name, arguments).await,
+    };
+
+    match result {
+      Ok(result) => tool_result_text(&result),
+      // Schema violations are listed so the model can fix the arguments and call again
+      Err(e) if e.is::<InvalidArguments>() => {
+        warn!("Agent: Rejected arguments for {}: {}", tool_name, e);
+        format!("{}\nCorrect the arguments and call the tool again.", e)
+      }
+      Err(e) => {
+        warn!("Agent: Tool {} failed: {}", tool_name, e);
+        format!("Tool error: {}", e)
+      }
+    }
+  }
+}
+
+// Join the text items of an MCP tools/call result
+fn tool_result_text(result: &Value) -> String {
+  let text = result["content"]
+    .as_array()
+    .map(|items| {
+      items
+        .iter()
+        .filter_map(|item| item["text"].as_str())
+        .collect::<Vec<_>>()
+        .join("\n")
+    })
+    .unwrap_or_default();
+
+  if text.is_empty() {
+    "No content".to_string()
+  } else {
+    text
+  }
+}
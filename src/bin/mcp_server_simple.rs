@@ -1,8 +1,33 @@
 use deepseek_agent::mcp_server_simple::SimpleMcpServer;
 use dotenv::dotenv;
-use std::io;
+use std::net::SocketAddr;
+use std::{env, io};
 use tracing::{Level, info};
-use tracing_subscriber;
+
+const HTTP_FLAG: &str = "--http";
+const DEFAULT_HTTP_ADDR: &str = "127.0.0.1:3000";
+
+// `--http [addr]` (or `--http=<addr>`) selects the Streamable HTTP transport;
+// without it the server speaks stdio
+fn http_addr_from_args() -> Result<Option<SocketAddr>, Box<dyn std::error::Error>> {
+  let mut args = env::args().skip(1).peekable();
+  while let Some(arg) = args.next() {
+    let addr = if arg == HTTP_FLAG {
+      args
+        .next_if(|next| !next.starts_with("--"))
+        .unwrap_or_else(|| DEFAULT_HTTP_ADDR.to_string())
+    } else if let Some(addr) = arg.strip_prefix(HTTP_FLAG).and_then(|rest| rest.strip_prefix('=')) {
+      addr.to_string()
+    } else {
+      continue;
+    };
+    let addr = addr
+      .parse()
+      .map_err(|e| format!("Invalid {} address `{}`: {}", HTTP_FLAG, addr, e))?;
+    return Ok(Some(addr));
+  }
+  Ok(None)
+}
 
 #[tokio::main]
 async fn main() -> Result<(), Box<dyn std::error::Error>> {
@@ -17,10 +42,30 @@ async fn main() -> Result<(), Box<dyn std::error::Error>> {
   info!("MCP Server: Environment variables loaded");
 
   // Create and run simple MCP server
-  let server = SimpleMcpServer::new()?;
-  info!("MCP Server: Simple MCP Server created, starting stdio server...");
-  
-  server.run_stdio().await?;
+  let mut server = SimpleMcpServer::new()?;
+  if let Ok(limit) = env::var("MCP_MAX_CONCURRENT_REQUESTS") {
+    let limit = limit
+      .parse()
+      .map_err(|e| format!("Invalid MCP_MAX_CONCURRENT_REQUESTS `{}`: {}", limit, e))?;
+    server = server.with_max_concurrent_requests(limit);
+  }
+  if let Ok(page_size) = env::var("MCP_PAGE_SIZE") {
+    let page_size = page_size
+      .parse()
+      .map_err(|e| format!("Invalid MCP_PAGE_SIZE `{}`: {}", page_size, e))?;
+    server = server.with_page_size(page_size);
+  }
+
+  match http_addr_from_args()? {
+    Some(addr) => {
+      info!("MCP Server: Simple MCP Server created, starting HTTP server...");
+      server.run_http(addr).await?;
+    }
+    None => {
+      info!("MCP Server: Simple MCP Server created, starting stdio server...");
+      server.run_stdio().await?;
+    }
+  }
 
   Ok(())
 }
\ No newline at end of file
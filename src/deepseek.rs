@@ -0,0 +1,795 @@
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+use std::collections::VecDeque;
+use std::env;
+use std::fmt;
+use std::sync::OnceLock;
+
+use crate::sse::{SseEvent, SseParser};
+
+// One connection pool shared by every DeepSeekClient in the process
+fn shared_http_client() -> &'static reqwest::Client {
+  static HTTP_CLIENT: OnceLock<reqwest::Client> = OnceLock::new();
+  HTTP_CLIENT.get_or_init(reqwest::Client::new)
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum Role {
+  System,
+  User,
+  Assistant,
+  Tool,
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct ChatMessage {
+  pub role: Role,
+  #[serde(default)]
+  pub content: Option<String>,
+  // Chain of thought returned by deepseek-reasoner. The API rejects requests
+  // that send it back, so callers must strip it before replaying history.
+  #[serde(default, skip_serializing_if = "Option::is_none")]
+  pub reasoning_content: Option<String>,
+  #[serde(default, skip_serializing_if = "Option::is_none")]
+  pub tool_calls: Option<Vec<ToolCall>>,
+  #[serde(default, skip_serializing_if = "Option::is_none")]
+  pub tool_call_id: Option<String>,
+}
+
+impl ChatMessage {
+  pub fn system(content: impl Into<String>) -> Self {
+    Self::text(Role::System, content)
+  }
+
+  pub fn user(content: impl Into<String>) -> Self {
+    Self::text(Role::User, content)
+  }
+
+  pub fn assistant(content: impl Into<String>) -> Self {
+    Self::text(Role::Assistant, content)
+  }
+
+  pub fn tool(tool_call_id: impl Into<String>, content: impl Into<String>) -> Self {
+    Self {
+      role: Role::Tool,
+      content: Some(content.into()),
+      reasoning_content: None,
+      tool_calls: None,
+      tool_call_id: Some(tool_call_id.into()),
+    }
+  }
+
+  fn text(role: Role, content: impl Into<String>) -> Self {
+    Self {
+      role,
+      content: Some(content.into()),
+      reasoning_content: None,
+      tool_calls: None,
+      tool_call_id: None,
+    }
+  }
+
+  // Copy of the message without reasoning_content, safe to send back to the API
+  pub fn without_reasoning(&self) -> Self {
+    Self {
+      reasoning_content: None,
+      ..self.clone()
+    }
+  }
+
+  // Tool calls requested by an assistant message, empty if there are none
+  pub fn tool_calls(&self) -> &[ToolCall] {
+    self.tool_calls.as_deref().unwrap_or_default()
+  }
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct ToolCall {
+  pub id: String,
+  #[serde(rename = "type", default = "function_type")]
+  pub call_type: String,
+  pub function: FunctionCall,
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct FunctionCall {
+  pub name: String,
+  // JSON-encoded arguments exactly as produced by the model
+  pub arguments: String,
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct Tool {
+  #[serde(rename = "type")]
+  pub tool_type: String,
+  pub function: FunctionDefinition,
+}
+
+impl Tool {
+  pub fn function(name: impl Into<String>, description: impl Into<String>, parameters: Value) -> Self {
+    Self {
+      tool_type: function_type(),
+      function: FunctionDefinition {
+        name: name.into(),
+        description: description.into(),
+        parameters,
+      },
+    }
+  }
+
+  // Convert an MCP tools/list entry into a DeepSeek function definition
+  pub fn from_mcp(tool: &Value) -> Self {
+    Self::function(
+      tool["name"].as_str().unwrap_or_default(),
+      tool["description"].as_str().unwrap_or_default(),
+      tool["inputSchema"].clone(),
+    )
+  }
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct FunctionDefinition {
+  pub name: String,
+  pub description: String,
+  pub parameters: Value,
+}
+
+fn function_type() -> String {
+  "function".to_string()
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(untagged)]
+pub enum ToolChoice {
+  Mode(ToolChoiceMode),
+  Function {
+    #[serde(rename = "type")]
+    choice_type: String,
+    function: ToolChoiceFunction,
+  },
+}
+
+impl ToolChoice {
+  pub fn auto() -> Self {
+    ToolChoice::Mode(ToolChoiceMode::Auto)
+  }
+
+  pub fn none() -> Self {
+    ToolChoice::Mode(ToolChoiceMode::None)
+  }
+
+  pub fn required() -> Self {
+    ToolChoice::Mode(ToolChoiceMode::Required)
+  }
+
+  // Force the model to call one specific function
+  pub fn function(name: impl Into<String>) -> Self {
+    ToolChoice::Function {
+      choice_type: function_type(),
+      function: ToolChoiceFunction { name: name.into() },
+    }
+  }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum ToolChoiceMode {
+  None,
+  Auto,
+  Required,
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct ToolChoiceFunction {
+  pub name: String,
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct ChatCompletionRequest {
+  pub model: String,
+  pub messages: Vec<ChatMessage>,
+  #[serde(skip_serializing_if = "Vec::is_empty", default)]
+  pub tools: Vec<Tool>,
+  #[serde(skip_serializing_if = "Option::is_none", default)]
+  pub tool_choice: Option<ToolChoice>,
+  #[serde(skip_serializing_if = "Option::is_none", default)]
+  pub temperature: Option<f32>,
+  #[serde(skip_serializing_if = "Option::is_none", default)]
+  pub max_tokens: Option<u32>,
+  #[serde(skip_serializing_if = "Option::is_none", default)]
+  pub stream: Option<bool>,
+}
+
+impl ChatCompletionRequest {
+  pub fn new(model: impl Into<String>, messages: Vec<ChatMessage>) -> Self {
+    Self {
+      model: model.into(),
+      messages,
+      tools: Vec::new(),
+      tool_choice: None,
+      temperature: None,
+      max_tokens: None,
+      stream: None,
+    }
+  }
+
+  // Offer tools to the model and let it decide when to call them
+  pub fn with_tools(mut self, tools: Vec<Tool>) -> Self {
+    self.tool_choice = (!tools.is_empty()).then(ToolChoice::auto);
+    self.tools = tools;
+    self
+  }
+
+  pub fn with_tool_choice(mut self, tool_choice: ToolChoice) -> Self {
+    self.tool_choice = Some(tool_choice);
+    self
+  }
+
+  pub fn with_temperature(mut self, temperature: f32) -> Self {
+    self.temperature = Some(temperature);
+    self
+  }
+
+  pub fn with_max_tokens(mut self, max_tokens: u32) -> Self {
+    self.max_tokens = Some(max_tokens);
+    self
+  }
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct ChatCompletionResponse {
+  pub id: String,
+  #[serde(default)]
+  pub object: String,
+  #[serde(default)]
+  pub created: u64,
+  pub model: String,
+  pub choices: Vec<Choice>,
+  #[serde(default)]
+  pub usage: Option<Usage>,
+}
+
+impl ChatCompletionResponse {
+  // The first choice's message, which is the only one DeepSeek returns
+  pub fn message(&self) -> Option<&ChatMessage> {
+    self.choices.first().map(|choice| &choice.message)
+  }
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct Choice {
+  pub index: u32,
+  pub message: ChatMessage,
+  #[serde(default)]
+  pub finish_reason: Option<FinishReason>,
+}
+
+// One `data:` payload of a streamed chat completion
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct ChatCompletionChunk {
+  pub id: String,
+  #[serde(default)]
+  pub model: String,
+  #[serde(default)]
+  pub choices: Vec<ChunkChoice>,
+  #[serde(default)]
+  pub usage: Option<Usage>,
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct ChunkChoice {
+  pub index: u32,
+  #[serde(default)]
+  pub delta: Delta,
+  #[serde(default)]
+  pub finish_reason: Option<FinishReason>,
+}
+
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+pub struct Delta {
+  #[serde(default)]
+  pub role: Option<Role>,
+  #[serde(default)]
+  pub content: Option<String>,
+  #[serde(default)]
+  pub reasoning_content: Option<String>,
+  #[serde(default)]
+  pub tool_calls: Option<Vec<ToolCallDelta>>,
+}
+
+// A fragment of a tool call; fragments sharing an index belong to the same call
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct ToolCallDelta {
+  pub index: usize,
+  #[serde(default)]
+  pub id: Option<String>,
+  #[serde(default)]
+  pub function: Option<FunctionCallDelta>,
+}
+
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+pub struct FunctionCallDelta {
+  #[serde(default)]
+  pub name: Option<String>,
+  #[serde(default)]
+  pub arguments: Option<String>,
+}
+
+// Reassembles streamed tool_calls fragments into complete calls
+#[derive(Debug, Default)]
+pub struct ToolCallAccumulator {
+  calls: Vec<ToolCall>,
+}
+
+impl ToolCallAccumulator {
+  pub fn push(&mut self, delta: &ToolCallDelta) {
+    while self.calls.len() <= delta.index {
+      self.calls.push(ToolCall {
+        id: String::new(),
+        call_type: function_type(),
+        function: FunctionCall {
+          name: String::new(),
+          arguments: String::new(),
+        },
+      });
+    }
+
+    let call = &mut self.calls[delta.index];
+    if let Some(id) = &delta.id {
+      call.id.push_str(id);
+    }
+    if let Some(function) = &delta.function {
+      if let Some(name) = &function.name {
+        call.function.name.push_str(name);
+      }
+      if let Some(arguments) = &function.arguments {
+        call.function.arguments.push_str(arguments);
+      }
+    }
+  }
+
+  pub fn is_empty(&self) -> bool {
+    self.calls.is_empty()
+  }
+
+  pub fn into_tool_calls(self) -> Vec<ToolCall> {
+    self.calls
+  }
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum StreamEvent {
+  // A fragment of the assistant's answer
+  Content(String),
+  // A fragment of deepseek-reasoner's reasoning_content
+  Reasoning(String),
+  // The stream ended; `message` holds the full content and assembled tool calls
+  Finished(StreamOutcome),
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct StreamOutcome {
+  pub message: ChatMessage,
+  pub finish_reason: Option<FinishReason>,
+  pub usage: Option<Usage>,
+}
+
+// Server-sent event stream of a chat completion, read with `next()`
+pub struct ChatStream {
+  response: reqwest::Response,
+  parser: SseParser,
+  state: StreamState,
+}
+
+impl ChatStream {
+  fn new(response: reqwest::Response) -> Self {
+    Self {
+      response,
+      parser: SseParser::new(),
+      state: StreamState::default(),
+    }
+  }
+
+  // Next event, or None once `Finished` has been returned
+  pub async fn next(&mut self) -> Option<Result<StreamEvent, Box<dyn std::error::Error>>> {
+    loop {
+      if let Some(event) = self.state.pending.pop_front() {
+        return Some(Ok(event));
+      }
+      if self.state.finished {
+        return None;
+      }
+
+      let events = match self.response.chunk().await {
+        Ok(Some(bytes)) => self.parser.feed(&bytes),
+        Ok(None) => {
+          let rest = self.parser.finish().into_iter().collect::<Vec<_>>();
+          if rest.is_empty() {
+            self.state.finish();
+          }
+          rest
+        }
+        Err(e) => return Some(Err(e.into())),
+      };
+
+      for event in &events {
+        if let Err(e) = self.state.handle(event) {
+          return Some(Err(e));
+        }
+      }
+    }
+  }
+
+  // Drain the stream, passing Content and Reasoning fragments to `on_delta`
+  pub async fn collect(
+    mut self,
+    mut on_delta: impl FnMut(&StreamEvent),
+  ) -> Result<StreamOutcome, Box<dyn std::error::Error>> {
+    while let Some(event) = self.next().await {
+      match event? {
+        StreamEvent::Finished(outcome) => return Ok(outcome),
+        delta => on_delta(&delta),
+      }
+    }
+    Err("Chat stream ended without a final message".into())
+  }
+}
+
+// What the events of a stream added up to so far, and the StreamEvents not
+// handed out yet
+#[derive(Default)]
+struct StreamState {
+  pending: VecDeque<StreamEvent>,
+  content: String,
+  reasoning: String,
+  tool_calls: ToolCallAccumulator,
+  finish_reason: Option<FinishReason>,
+  usage: Option<Usage>,
+  finished: bool,
+}
+
+impl StreamState {
+  // Events after `[DONE]` are ignored
+  fn handle(&mut self, event: &SseEvent) -> Result<(), Box<dyn std::error::Error>> {
+    if self.finished {
+      return Ok(());
+    }
+    if event.data == "[DONE]" {
+      self.finish();
+      return Ok(());
+    }
+    let chunk: ChatCompletionChunk = serde_json::from_str(&event.data)
+      .map_err(|e| format!("Invalid stream chunk {}: {}", event.data, e))?;
+    self.apply(chunk);
+    Ok(())
+  }
+
+  fn apply(&mut self, chunk: ChatCompletionChunk) {
+    if chunk.usage.is_some() {
+      self.usage = chunk.usage;
+    }
+    for choice in chunk.choices {
+      if let Some(reasoning) = choice.delta.reasoning_content.filter(|r| !r.is_empty()) {
+        self.reasoning.push_str(&reasoning);
+        self.pending.push_back(StreamEvent::Reasoning(reasoning));
+      }
+      if let Some(content) = choice.delta.content.filter(|c| !c.is_empty()) {
+        self.content.push_str(&content);
+        self.pending.push_back(StreamEvent::Content(content));
+      }
+      for delta in choice.delta.tool_calls.iter().flatten() {
+        self.tool_calls.push(delta);
+      }
+      if choice.finish_reason.is_some() {
+        self.finish_reason = choice.finish_reason;
+      }
+    }
+  }
+
+  fn finish(&mut self) {
+    if self.finished {
+      return;
+    }
+    self.finished = true;
+
+    let tool_calls = std::mem::take(&mut self.tool_calls);
+    let reasoning = std::mem::take(&mut self.reasoning);
+    let message = ChatMessage {
+      role: Role::Assistant,
+      content: Some(std::mem::take(&mut self.content)),
+      reasoning_content: (!reasoning.is_empty()).then_some(reasoning),
+      tool_calls: (!tool_calls.is_empty()).then(|| tool_calls.into_tool_calls()),
+      tool_call_id: None,
+    };
+    self.pending.push_back(StreamEvent::Finished(StreamOutcome {
+      message,
+      finish_reason: self.finish_reason,
+      usage: self.usage.clone(),
+    }));
+  }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum FinishReason {
+  Stop,
+  Length,
+  ToolCalls,
+  ContentFilter,
+  InsufficientSystemResource,
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct Usage {
+  pub prompt_tokens: u32,
+  pub completion_tokens: u32,
+  pub total_tokens: u32,
+}
+
+#[derive(Debug)]
+pub struct DeepSeekApiError {
+  pub status: reqwest::StatusCode,
+  pub body: String,
+}
+
+impl fmt::Display for DeepSeekApiError {
+  fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+    write!(f, "DeepSeek API error ({}): {}", self.status, self.body)
+  }
+}
+
+impl std::error::Error for DeepSeekApiError {}
+
+#[derive(Debug, Clone)]
+pub struct DeepSeekClient {
+  http: reqwest::Client,
+  api_key: String,
+  endpoint: String,
+  model: String,
+}
+
+impl DeepSeekClient {
+  pub fn new(api_key: impl Into<String>, endpoint: impl Into<String>, model: impl Into<String>) -> Self {
+    Self {
+      http: shared_http_client().clone(),
+      api_key: api_key.into(),
+      endpoint: endpoint.into(),
+      model: model.into(),
+    }
+  }
+
+  // Read DEEPSEEK_API_KEY, DEEPSEEK_API_URL and MODEL_NAME from the environment
+  pub fn from_env() -> Result<Self, Box<dyn std::error::Error>> {
+    Ok(Self::new(
+      env::var("DEEPSEEK_API_KEY")?,
+      env::var("DEEPSEEK_API_URL")?,
+      env::var("MODEL_NAME")?,
+    ))
+  }
+
+  pub fn model(&self) -> &str {
+    &self.model
+  }
+
+  // Start a request for the configured model
+  pub fn request(&self, messages: Vec<ChatMessage>) -> ChatCompletionRequest {
+    ChatCompletionRequest::new(self.model.clone(), messages)
+  }
+
+  pub async fn chat(
+    &self,
+    request: &ChatCompletionRequest,
+  ) -> Result<ChatCompletionResponse, Box<dyn std::error::Error>> {
+    let response = self.send(request).await?;
+    Ok(response.json::<ChatCompletionResponse>().await?)
+  }
+
+  // Same as `chat` but with `stream: true`, returning the event stream
+  pub async fn chat_stream(
+    &self,
+    request: &ChatCompletionRequest,
+  ) -> Result<ChatStream, Box<dyn std::error::Error>> {
+    let mut request = request.clone();
+    request.stream = Some(true);
+
+    let response = self.send(&request).await?;
+    Ok(ChatStream::new(response))
+  }
+
+  async fn send(
+    &self,
+    request: &ChatCompletionRequest,
+  ) -> Result<reqwest::Response, Box<dyn std::error::Error>> {
+    let response = self
+      .http
+      .post(&self.endpoint)
+      .bearer_auth(&self.api_key)
+      .json(request)
+      .send()
+      .await?;
+
+    let status = response.status();
+    if !status.is_success() {
+      let body = response.text().await.unwrap_or_default();
+      return Err(DeepSeekApiError { status, body }.into());
+    }
+
+    Ok(response)
+  }
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+  use serde_json::json;
+
+  fn tool_call(id: &str, name: &str, arguments: &str) -> ToolCall {
+    ToolCall {
+      id: id.to_string(),
+      call_type: function_type(),
+      function: FunctionCall {
+        name: name.to_string(),
+        arguments: arguments.to_string(),
+      },
+    }
+  }
+
+  fn chunk(delta: Value) -> ChatCompletionChunk {
+    serde_json::from_value(json!({
+      "id": "chunk",
+      "choices": [{"index": 0, "delta": delta}]
+    }))
+    .unwrap()
+  }
+
+  // Everything the stream hands out for `body` fed in pieces of `size` bytes
+  fn stream_events(body: &str, size: usize) -> Vec<StreamEvent> {
+    let mut parser = SseParser::new();
+    let mut state = StreamState::default();
+    for piece in body.as_bytes().chunks(size) {
+      for event in parser.feed(piece) {
+        state.handle(&event).unwrap();
+      }
+    }
+    // Once the response body ends
+    if let Some(event) = parser.finish() {
+      state.handle(&event).unwrap();
+    }
+    state.finish();
+    state.pending.into_iter().collect()
+  }
+
+  fn sse(chunks: &[Value]) -> String {
+    chunks
+      .iter()
+      .map(|chunk| format!("data: {}\n\n", chunk))
+      .collect()
+  }
+
+  fn delta_chunk(delta: Value, finish_reason: Option<&str>) -> Value {
+    json!({
+      "id": "chunk",
+      "choices": [{"index": 0, "delta": delta, "finish_reason": finish_reason}]
+    })
+  }
+
+  #[test]
+  fn tool_call_fragments_are_joined_by_index() {
+    let mut state = StreamState::default();
+    let fragments = [
+      json!([{"index": 0, "id": "call_0", "function": {"name": "get_", "arguments": ""}}]),
+      json!([{"index": 1, "id": "call_1", "function": {"name": "search", "arguments": "{\"qu"}}]),
+      json!([{"index": 0, "function": {"name": "weather", "arguments": "{\"location\":"}}]),
+      json!([{"index": 1, "function": {"arguments": "ery\": \"rust\"}"}}]),
+      json!([{"index": 0, "function": {"arguments": " \"上海\"}"}}]),
+    ];
+    for fragment in fragments {
+      state.apply(chunk(json!({ "tool_calls": fragment })));
+    }
+    state.finish();
+
+    let Some(StreamEvent::Finished(outcome)) = state.pending.pop_front() else {
+      panic!("stream did not finish");
+    };
+    let calls = outcome.message.tool_calls.unwrap();
+    assert_eq!(
+      calls,
+      [
+        tool_call("call_0", "get_weather", "{\"location\": \"上海\"}"),
+        tool_call("call_1", "search", "{\"query\": \"rust\"}"),
+      ]
+    );
+    for call in &calls {
+      serde_json::from_str::<Value>(&call.function.arguments).unwrap();
+    }
+  }
+
+  #[test]
+  fn accumulator_fills_gaps_in_indices() {
+    let mut calls = ToolCallAccumulator::default();
+    assert!(calls.is_empty());
+    let delta: ToolCallDelta =
+      serde_json::from_value(json!({"index": 1, "id": "b", "function": {"name": "x"}})).unwrap();
+    calls.push(&delta);
+    assert_eq!(
+      calls.into_tool_calls(),
+      [tool_call("", "", ""), tool_call("b", "x", "")]
+    );
+  }
+
+  #[test]
+  fn content_and_reasoning_are_streamed_and_collected() {
+    let body = sse(&[
+      delta_chunk(json!({"role": "assistant", "reasoning_content": "想"}), None),
+      delta_chunk(json!({"reasoning_content": "一想", "content": ""}), None),
+      delta_chunk(json!({"content": "晴"}), None),
+      delta_chunk(json!({"content": "天"}), Some("stop")),
+      json!({
+        "id": "chunk",
+        "choices": [],
+        "usage": {"prompt_tokens": 3, "completion_tokens": 4, "total_tokens": 7}
+      }),
+    ]) + "data: [DONE]\n\n";
+
+    for size in [1, 7, body.len()] {
+      let events = stream_events(&body, size);
+      assert_eq!(events.len(), 5, "{:?}", events);
+      assert_eq!(events[0], StreamEvent::Reasoning("想".to_string()));
+      assert_eq!(events[1], StreamEvent::Reasoning("一想".to_string()));
+      assert_eq!(events[2], StreamEvent::Content("晴".to_string()));
+      assert_eq!(events[3], StreamEvent::Content("天".to_string()));
+      let StreamEvent::Finished(outcome) = &events[4] else {
+        panic!("stream did not finish");
+      };
+      assert_eq!(outcome.message.role, Role::Assistant);
+      assert_eq!(outcome.message.content.as_deref(), Some("晴天"));
+      assert_eq!(outcome.message.reasoning_content.as_deref(), Some("想一想"));
+      assert_eq!(outcome.message.tool_calls, None);
+      assert_eq!(outcome.finish_reason, Some(FinishReason::Stop));
+      assert_eq!(outcome.usage.as_ref().map(|usage| usage.total_tokens), Some(7));
+    }
+  }
+
+  #[test]
+  fn done_ends_the_stream() {
+    let body = format!(
+      ": keep-alive\r\n\r\ndata: {}\r\n\r\ndata: [DONE]\r\n\r\ndata: {}\r\n\r\n",
+      delta_chunk(json!({"content": "a"}), None),
+      delta_chunk(json!({"content": "ignored"}), None),
+    );
+    let events = stream_events(&body, 5);
+    assert_eq!(events.len(), 2, "{:?}", events);
+    let StreamEvent::Finished(outcome) = &events[1] else {
+      panic!("stream did not finish");
+    };
+    assert_eq!(outcome.message.content.as_deref(), Some("a"));
+  }
+
+  #[test]
+  fn unterminated_last_event_is_applied() {
+    // No [DONE] and no blank line after the last chunk
+    let body = format!(
+      "data: {}\n\ndata: {}",
+      delta_chunk(json!({"content": "a"}), None),
+      delta_chunk(json!({"content": "b"}), Some("length")),
+    );
+    let events = stream_events(&body, 4);
+    assert_eq!(events.len(), 3, "{:?}", events);
+    let StreamEvent::Finished(outcome) = &events[2] else {
+      panic!("stream did not finish");
+    };
+    assert_eq!(outcome.message.content.as_deref(), Some("ab"));
+    assert_eq!(outcome.finish_reason, Some(FinishReason::Length));
+  }
+
+  #[test]
+  fn invalid_chunks_are_errors() {
+    let mut state = StreamState::default();
+    let error = state.handle(&SseEvent {
+      data: "{not json".to_string(),
+      ..Default::default()
+    });
+    assert!(error.unwrap_err().to_string().starts_with("Invalid stream chunk"));
+  }
+}
@@ -1,4 +1,14 @@
 pub mod tools;
+pub mod mcp_registry;
 pub mod mcp_tools;
 pub mod mcp_server_simple;
-pub mod mcp_client;
\ No newline at end of file
+pub mod mcp_server_http;
+pub mod mcp_server_stdio;
+pub mod mcp_protocol;
+pub mod mcp_schema;
+pub mod mcp_client;
+pub mod mcp_host;
+pub mod mcp_config;
+pub mod sse;
+pub mod deepseek;
+pub mod agent;
\ No newline at end of file
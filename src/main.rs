@@ -1,108 +1,71 @@
+use deepseek_agent::agent::Agent;
+use deepseek_agent::deepseek::{DeepSeekClient, StreamEvent};
+use deepseek_agent::mcp_client::ServerCommand;
+use deepseek_agent::mcp_config::McpConfig;
+use deepseek_agent::mcp_host::McpHost;
 use dotenv::dotenv;
-use reqwest;
-use serde_json::{Value, json};
 use std::env;
+use std::io::{self, Write};
 use tracing::{Level, info};
-use tracing_subscriber;
-
-mod tools;
-mod mcp_tools;
-mod mcp_client;
-
-use mcp_client::McpClient;
 
 async fn chat_with_mcp_server(user_query: &str) -> Result<(), Box<dyn std::error::Error>> {
-  let api_key = env::var("DEEPSEEK_API_KEY").unwrap();
-  let endpoint = env::var("DEEPSEEK_API_URL").unwrap().to_string();
-
-  // Initialize MCP Client and connect to server
-  let mut mcp_client = McpClient::new("mcp_server").await?;
-  info!("MCP Client: Connected to MCP server");
-
-  // Initialize MCP connection
-  let _server_info = mcp_client.initialize().await?;
-  info!("MCP Client: Server initialized successfully");
-
-  // Get available tools from MCP server
-  let tools = mcp_client.list_tools().await?;
-  info!("MCP Client: Available tools: {}", tools.len());
-
-  // 构建请求体
-  let body = json!({
-      "model": env::var("MODEL_NAME").unwrap(),
-      "messages": [
-          {
-              "role": "system",
-              "content": "你是一个专业的助手，可以：\n1. 提供天气信息和穿衣建议\n2. 搜索互联网获取实时信息\n请根据用户的问题，选择合适的工具来提供帮助。\n\n这是一个基于Model Context Protocol (MCP)的工具系统。"
-          },
-          {"role": "user", "content": user_query}
-      ],
-      "tools": tools.iter().map(|tool| {
-        json!({
-          "type": "function",
-          "function": {
-            "name": tool["name"],
-            "description": tool["description"],
-            "parameters": tool["inputSchema"]
-          }
-        })
-      }).collect::<Vec<_>>(),
-      "tool_choice": "auto",
-  });
-
-  // 发送初次请求
-  let response = reqwest::Client::new()
-    .post(&endpoint)
-    .header("Authorization", format!("Bearer {}", api_key))
-    .json(&body)
-    .send()
-    .await?
-    .json::<Value>()
-    .await?;
-  info!(
-    "MCP LLM响应结果: {}",
-    serde_json::to_string_pretty(&response).unwrap()
-  );
-
-  // 处理MCP工具调用
-  if let Some(tool_calls) = response["choices"][0]["message"]["tool_calls"].as_array() {
-    for call in tool_calls {
-      let tool_name = call["function"]["name"].as_str().unwrap();
-      let arguments: Value = serde_json::from_str(call["function"]["arguments"].as_str().unwrap())?;
-      
-      // Use MCP server tool execution
-      let tool_result = mcp_client.call_tool(tool_name, arguments).await?;
-
-      // Extract result from MCP response
-      let content = tool_result["content"][0]["text"].as_str().unwrap_or("No content");
-      let system_prompt = "请根据工具返回的结果给出准确、有帮助的回答。".to_string();
-
-      let final_response = reqwest::Client::new()
-        .post(&endpoint)
-        .header("Authorization", format!("Bearer {}", api_key))
-        .json(&json!({
-            "model": env::var("MODEL_NAME").unwrap(),
-            "messages": [
-                {"role": "system", "content": system_prompt},
-                {"role": "assistant", "content": null, "tool_calls": [call]},
-                {
-                    "role": "tool",
-                    "content": content,
-                    "tool_call_id": call["id"]
-                }
-            ]
-        }))
-        .send()
-        .await?
-        .json::<Value>()
-        .await?;
-
-      info!(
-        "MCP最终回答: {}",
-        final_response["choices"][0]["message"]["content"]
-      );
+  // 启动并初始化 MCP 服务器，所有服务器的工具会合并到同一个工具列表
+  let mcp_host = match McpConfig::path_from_args_or_env()? {
+    // 通过 --mcp-config 或 MCP_CONFIG 指定的 mcpServers 配置文件
+    Some(path) => {
+      info!("MCP Host: Loading config from {}", path.display());
+      McpHost::from_config(&McpConfig::from_file(&path)?).await?
     }
-  }
+    // 默认在源码目录中通过 cargo 启动 MCP 服务器，也可以指定预编译的服务器程序，
+    // 或通过 MCP_SERVER_URL 连接已在运行的 HTTP 服务器
+    None => {
+      let mut mcp_host = McpHost::new();
+      match env::var("MCP_SERVER_URL") {
+        Ok(url) => mcp_host.connect_http("local", &url).await?,
+        Err(_) => {
+          let server_command = match env::var("MCP_SERVER_COMMAND") {
+            Ok(program) => ServerCommand::new(program),
+            Err(_) => ServerCommand::cargo_mcp_server(),
+          };
+          mcp_host.connect("local", server_command).await?
+        }
+      }
+      mcp_host
+    }
+  };
+  info!("MCP Host: Connected to MCP server(s)");
+
+  // 多轮调用：工具结果会回传给模型，直到模型给出最终回答
+  let deepseek = DeepSeekClient::from_env()?;
+  // 工具执行进度输出到 stderr
+  let mut agent = Agent::new(deepseek, mcp_host)
+    .with_reasoning_log(env::var("LOG_REASONING").is_ok())
+    .with_tool_progress(|tool, progress| {
+      let step = match progress.total {
+        Some(total) => format!("{}/{}", progress.progress, total),
+        None => progress.progress.to_string(),
+      };
+      eprintln!(
+        "[{}] {} {}",
+        tool,
+        step,
+        progress.message.as_deref().unwrap_or_default()
+      );
+    });
+
+  // 流式输出：模型生成的内容实时打印到终端，推理过程输出到 stderr
+  let reply = agent
+    .run_streaming(user_query, &mut |delta| match delta {
+      StreamEvent::Content(text) => {
+        print!("{}", text);
+        let _ = io::stdout().flush();
+      }
+      StreamEvent::Reasoning(text) => eprint!("{}", text),
+      StreamEvent::Finished(_) => {}
+    })
+    .await?;
+  println!();
+  info!("MCP最终回答: {}", reply.answer);
 
   Ok(())
 }
@@ -121,4 +84,4 @@ async fn main() -> Result<(), Box<dyn std::error::Error>> {
   chat_with_mcp_server(search_query).await?;
 
   Ok(())
-}
+}
\ No newline at end of file
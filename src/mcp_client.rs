@@ -1,114 +1,667 @@
+use rmcp::model::{
+  CancelledNotificationParam, ClientCapabilities, ClientInfo, ClientRequest, ClientResult,
+  ErrorData, Implementation, JsonRpcMessage, Meta, NumberOrString, ProgressNotificationParam,
+  ProgressToken, RequestId, ServerNotification, ServerRequest,
+};
+use rmcp::service::{
+  NotificationContext, PeerRequestOptions, RequestContext, RoleClient, RunningService,
+  RxJsonRpcMessage, TxJsonRpcMessage,
+};
+use rmcp::transport::{IntoTransport, StreamableHttpClientTransport, TokioChildProcess, Transport};
+use rmcp::{Peer, Service, ServiceError, ServiceExt};
 use serde_json::{Value, json};
+use std::collections::{HashMap, HashSet};
+use std::path::PathBuf;
 use std::process::Stdio;
-use tokio::io::{AsyncBufReadExt, AsyncWriteExt, BufReader};
-use tokio::process::{Child, Command};
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::{Arc, Mutex};
+use tokio::process::Command;
+use tokio::sync::mpsc;
 use tracing::info;
 
+use crate::mcp_protocol::{JsonRpcError, LATEST_PROTOCOL_VERSION, is_supported_version};
+use crate::mcp_schema::{InvalidArguments, validate_arguments};
+
+// How to launch a stdio MCP server: any executable with its arguments,
+// extra environment variables and working directory.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct ServerCommand {
+  pub program: String,
+  pub args: Vec<String>,
+  pub env: HashMap<String, String>,
+  pub cwd: Option<PathBuf>,
+}
+
+impl ServerCommand {
+  pub fn new(program: impl Into<String>) -> Self {
+    Self {
+      program: program.into(),
+      ..Default::default()
+    }
+  }
+
+  // `cargo run --bin mcp_server`, only usable from inside this source tree
+  pub fn cargo_mcp_server() -> Self {
+    Self::new("cargo").args(["run", "--quiet", "--bin", "mcp_server"])
+  }
+
+  pub fn arg(mut self, arg: impl Into<String>) -> Self {
+    self.args.push(arg.into());
+    self
+  }
+
+  pub fn args<I, S>(mut self, args: I) -> Self
+  where
+    I: IntoIterator<Item = S>,
+    S: Into<String>,
+  {
+    self.args.extend(args.into_iter().map(Into::into));
+    self
+  }
+
+  pub fn env(mut self, key: impl Into<String>, value: impl Into<String>) -> Self {
+    self.env.insert(key.into(), value.into());
+    self
+  }
+
+  pub fn current_dir(mut self, cwd: impl Into<PathBuf>) -> Self {
+    self.cwd = Some(cwd.into());
+    self
+  }
+
+  pub(crate) fn to_command(&self) -> Command {
+    let mut command = Command::new(&self.program);
+    command.args(&self.args).envs(&self.env);
+    if let Some(cwd) = &self.cwd {
+      command.current_dir(cwd);
+    }
+    command
+  }
+}
+
+// What the server reported in its initialize result
+#[derive(Debug, Clone, PartialEq)]
+pub struct InitializeResult {
+  pub protocol_version: String,
+  pub capabilities: Value,
+  pub server_info: Value,
+  pub instructions: Option<String>,
+}
+
+// Handlers receive the message `params`
+pub type NotificationHandler = Arc<dyn Fn(Value) + Send + Sync>;
+pub type RequestHandler = Arc<dyn Fn(Value) -> Result<Value, String> + Send + Sync>;
+
+// One notifications/progress update for a request
+#[derive(Debug, Clone, PartialEq)]
+pub struct Progress {
+  pub progress: f64,
+  pub total: Option<f64>,
+  pub message: Option<String>,
+}
+
+impl From<ProgressNotificationParam> for Progress {
+  fn from(params: ProgressNotificationParam) -> Self {
+    Self {
+      progress: params.progress,
+      total: params.total,
+      message: params.message,
+    }
+  }
+}
+
+pub type ProgressHandler = Arc<dyn Fn(Progress) + Send + Sync>;
+
+#[derive(Default)]
+struct Handlers {
+  notifications: HashMap<String, NotificationHandler>,
+  requests: HashMap<String, RequestHandler>,
+  // Progress callbacks of in-flight requests, by progress token
+  progress: HashMap<String, ProgressHandler>,
+  // Streams handed out by resource_updates
+  resource_updates: Vec<mpsc::UnboundedSender<String>>,
+  // Bumped by every notifications/tools/list_changed
+  tools_generation: u64,
+}
+
+// rmcp client service: hands what the server sends to the registered handlers
+struct ClientHandlers {
+  handlers: Arc<Mutex<Handlers>>,
+}
+
+impl Service<RoleClient> for ClientHandlers {
+  async fn handle_request(
+    &self,
+    request: ServerRequest,
+    _context: RequestContext<RoleClient>,
+  ) -> Result<ClientResult, ErrorData> {
+    let message = serde_json::to_value(&request).unwrap_or_default();
+    let method = message["method"].as_str().unwrap_or_default();
+    info!("MCP Client: Server request: {}", method);
+
+    let handler = self.handlers.lock().unwrap().requests.get(method).cloned();
+    let result = match (method, handler) {
+      (_, Some(handler)) => {
+        handler(message["params"].clone()).map_err(JsonRpcError::internal_error)?
+      }
+      ("ping", None) => json!({}),
+      (_, None) => return Err(JsonRpcError::method_not_found(method).into()),
+    };
+    serde_json::from_value(result)
+      .map_err(|e| JsonRpcError::internal_error(format!("Invalid result: {}", e)).into())
+  }
+
+  async fn handle_notification(
+    &self,
+    notification: ServerNotification,
+    _context: NotificationContext<RoleClient>,
+  ) -> Result<(), ErrorData> {
+    if let ServerNotification::ToolListChangedNotification(_) = &notification {
+      info!("MCP Client: Server tool list changed");
+      self.handlers.lock().unwrap().tools_generation += 1;
+    }
+
+    // Resource updates go to the resource_updates streams and to an
+    // on_notification handler alike
+    let mut streamed = false;
+    if let ServerNotification::ResourceUpdatedNotification(updated) = &notification {
+      let mut handlers = self.handlers.lock().unwrap();
+      let streams = &mut handlers.resource_updates;
+      streams.retain(|stream| stream.send(updated.params.uri.clone()).is_ok());
+      streamed = !streams.is_empty();
+    }
+
+    let message = serde_json::to_value(&notification).unwrap_or_default();
+    let method = message["method"].as_str().unwrap_or_default();
+    let handler = self
+      .handlers
+      .lock()
+      .unwrap()
+      .notifications
+      .get(method)
+      .cloned();
+    match handler {
+      Some(handler) => handler(message["params"].clone()),
+      None if streamed => {}
+      None => info!("MCP Client: Unhandled notification: {}", method),
+    }
+    Ok(())
+  }
+
+  fn get_info(&self) -> ClientInfo {
+    ClientInfo {
+      meta: None,
+      protocol_version: serde_json::from_value(json!(LATEST_PROTOCOL_VERSION)).unwrap_or_default(),
+      capabilities: ClientCapabilities::default(),
+      client_info: Implementation {
+        name: "deepseek-agent".to_string(),
+        version: env!("CARGO_PKG_VERSION").to_string(),
+        ..Implementation::from_build_env()
+      },
+    }
+  }
+}
+
+// Client transport that hands progress notifications to their callbacks as
+// they are read. rmcp runs notification handlers on their own tasks, which
+// could let the response overtake the last progress update; here every
+// update is delivered before the messages that follow it are even read.
+struct ProgressTransport<T> {
+  inner: T,
+  handlers: Arc<Mutex<Handlers>>,
+}
+
+impl<T: Transport<RoleClient>> Transport<RoleClient> for ProgressTransport<T> {
+  type Error = T::Error;
+
+  fn send(
+    &mut self,
+    item: TxJsonRpcMessage<RoleClient>,
+  ) -> impl Future<Output = Result<(), Self::Error>> + Send + 'static {
+    self.inner.send(item)
+  }
+
+  async fn receive(&mut self) -> Option<RxJsonRpcMessage<RoleClient>> {
+    loop {
+      let message = self.inner.receive().await?;
+      if let JsonRpcMessage::Notification(notification) = &message
+        && let ServerNotification::ProgressNotification(progress) = &notification.notification
+      {
+        let token = token_key(&progress.params.progress_token);
+        let handler = self.handlers.lock().unwrap().progress.get(&token).cloned();
+        if let Some(handler) = handler {
+          handler(progress.params.clone().into());
+          continue;
+        }
+      }
+      return Some(message);
+    }
+  }
+
+  async fn close(&mut self) -> Result<(), Self::Error> {
+    self.inner.close().await
+  }
+}
+
+// Removes a progress callback once its request has finished
+struct ProgressGuard {
+  token: String,
+  handlers: Arc<Mutex<Handlers>>,
+}
+
+impl Drop for ProgressGuard {
+  fn drop(&mut self) {
+    self.handlers.lock().unwrap().progress.remove(&self.token);
+  }
+}
+
+// Cancels a request on the server with notifications/cancelled when the
+// future waiting for it is dropped before the response has arrived
+struct CancelOnDrop {
+  peer: Peer<RoleClient>,
+  id: Option<RequestId>,
+}
+
+impl Drop for CancelOnDrop {
+  fn drop(&mut self) {
+    let Some(request_id) = self.id.take() else {
+      return;
+    };
+    info!("MCP Client: Cancelling request {}", request_id);
+    let peer = self.peer.clone();
+    if let Ok(runtime) = tokio::runtime::Handle::try_current() {
+      runtime.spawn(async move {
+        let _ = peer
+          .notify_cancelled(CancelledNotificationParam {
+            request_id,
+            reason: Some("Request cancelled by client".to_string()),
+          })
+          .await;
+      });
+    }
+  }
+}
+
+// MCP client built on rmcp's client service, over a stdio server process or
+// a Streamable HTTP endpoint. rmcp runs the initialize handshake while
+// connecting and matches responses to requests, so any number of requests can
+// be in flight at once; notifications and server-to-client requests go to the
+// registered handlers.
 pub struct McpClient {
-  child: Child,
-  request_id: u64,
+  service: RunningService<RoleClient, ClientHandlers>,
+  handlers: Arc<Mutex<Handlers>>,
+  progress_token: AtomicU64,
+  server: InitializeResult,
+  // inputSchema of every tool seen in the last tools/list, by name
+  tool_schemas: Mutex<HashMap<String, Value>>,
+  // tools_generation as of the start of the last successful tools/list
+  listed_tools_generation: AtomicU64,
 }
 
 impl McpClient {
-  pub async fn new(server_command: &str) -> Result<Self, Box<dyn std::error::Error>> {
-    info!("MCP Client: Starting server process: {}", server_command);
-
-    let child = Command::new("cargo")
-      .args(&["run", "--bin", "mcp_server"])
-      .stdin(Stdio::piped())
-      .stdout(Stdio::piped())
+  // Start a stdio server process and connect to it
+  pub async fn new(server_command: ServerCommand) -> Result<Self, Box<dyn std::error::Error>> {
+    info!(
+      "MCP Client: Starting server process: {} {}",
+      server_command.program,
+      server_command.args.join(" ")
+    );
+    let (transport, _) = TokioChildProcess::builder(server_command.to_command())
       .stderr(Stdio::inherit())
-      .spawn()?;
+      .spawn()
+      .map_err(|e| {
+        format!(
+          "Failed to start MCP server `{}`: {}",
+          server_command.program, e
+        )
+      })?;
+    Self::with_transport(transport).await
+  }
+
+  // Connect to a Streamable HTTP server, e.g. `http://127.0.0.1:3000/mcp`
+  pub async fn connect_http(url: &str) -> Result<Self, Box<dyn std::error::Error>> {
+    info!("MCP Client: Connecting to {}", url);
+    Self::with_transport(StreamableHttpClientTransport::from_uri(url)).await
+  }
+
+  // Handshake over any rmcp client transport: propose our latest protocol
+  // version and check the server's answer
+  pub async fn with_transport<T, E, A>(transport: T) -> Result<Self, Box<dyn std::error::Error>>
+  where
+    T: IntoTransport<RoleClient, E, A>,
+    E: std::error::Error + Send + Sync + 'static,
+  {
+    let handlers = Arc::new(Mutex::new(Handlers::default()));
+    let transport = ProgressTransport {
+      inner: transport.into_transport(),
+      handlers: handlers.clone(),
+    };
+    let service = ClientHandlers {
+      handlers: handlers.clone(),
+    }
+    .serve(transport)
+    .await?;
+
+    let info = service
+      .peer_info()
+      .ok_or("MCP server did not answer initialize")?;
+    let protocol_version = info.protocol_version.to_string();
+    if !is_supported_version(&protocol_version) {
+      return Err(
+        format!(
+          "MCP server answered with unsupported protocol version `{}`",
+          protocol_version
+        )
+        .into(),
+      );
+    }
+
+    let server = InitializeResult {
+      protocol_version,
+      capabilities: serde_json::to_value(&info.capabilities)?,
+      server_info: serde_json::to_value(&info.server_info)?,
+      instructions: info.instructions.clone(),
+    };
+    info!(
+      "MCP Client: Initialized {} (protocol {})",
+      server.server_info["name"], server.protocol_version
+    );
 
     Ok(Self {
-      child,
-      request_id: 0,
+      service,
+      handlers,
+      progress_token: AtomicU64::new(0),
+      server,
+      tool_schemas: Mutex::default(),
+      listed_tools_generation: AtomicU64::new(0),
     })
   }
 
-  fn next_request_id(&mut self) -> u64 {
-    self.request_id += 1;
-    self.request_id
+  // Called with the params of every `method` notification from the server
+  pub fn on_notification(&self, method: &str, handler: impl Fn(Value) + Send + Sync + 'static) {
+    let mut handlers = self.handlers.lock().unwrap();
+    handlers
+      .notifications
+      .insert(method.to_string(), Arc::new(handler));
   }
 
+  // Answer server-to-client `method` requests; an Err is sent back as a
+  // JSON-RPC error. `ping` is answered automatically.
+  pub fn on_request(
+    &self,
+    method: &str,
+    handler: impl Fn(Value) -> Result<Value, String> + Send + Sync + 'static,
+  ) {
+    let mut handlers = self.handlers.lock().unwrap();
+    handlers
+      .requests
+      .insert(method.to_string(), Arc::new(handler));
+  }
+
+  // Send a request and wait for its result. Dropping the returned future
+  // before the response has arrived (e.g. through tokio::time::timeout or
+  // tokio::select!) cancels the request with notifications/cancelled.
   pub async fn send_request(
-    &mut self,
+    &self,
     method: &str,
     params: Value,
   ) -> Result<Value, Box<dyn std::error::Error>> {
-    let request_id = self.next_request_id();
-    let request = json!({
-      "jsonrpc": "2.0",
-      "id": request_id,
+    self.request(method, params, None).await
+  }
+
+  // The request is parsed into rmcp's typed ClientRequest, so malformed
+  // params fail here rather than on the server
+  async fn request(
+    &self,
+    method: &str,
+    params: Value,
+    meta: Option<Meta>,
+  ) -> Result<Value, Box<dyn std::error::Error>> {
+    info!("MCP Client: Sending request: {}", method);
+    let request: ClientRequest = serde_json::from_value(json!({
       "method": method,
       "params": params
-    });
+    }))
+    .map_err(|e| format!("Invalid {} request: {}", method, e))?;
 
-    info!("MCP Client: Sending request: {}", method);
+    let options = PeerRequestOptions {
+      timeout: None,
+      meta,
+    };
+    let handle = self
+      .service
+      .send_request_with_option(request, options)
+      .await
+      .map_err(request_error)?;
+    let mut cancel = CancelOnDrop {
+      peer: handle.peer.clone(),
+      id: Some(handle.id.clone()),
+    };
+    let response = handle.await_response().await;
+    cancel.id = None;
+
+    Ok(serde_json::to_value(response.map_err(request_error)?)?)
+  }
 
-    // Send request to server
-    let stdin = self.child.stdin.as_mut().ok_or("Failed to get stdin")?;
-    let request_str = serde_json::to_string(&request)?;
-    stdin.write_all(request_str.as_bytes()).await?;
-    stdin.write_all(b"\n").await?;
-    stdin.flush().await?;
+  pub async fn send_notification(
+    &self,
+    method: &str,
+    params: Value,
+  ) -> Result<(), Box<dyn std::error::Error>> {
+    info!("MCP Client: Sending notification: {}", method);
+    let notification = serde_json::from_value(json!({
+      "method": method,
+      "params": params
+    }))?;
+    self
+      .service
+      .send_notification(notification)
+      .await
+      .map_err(request_error)
+  }
 
-    // Read response from server
-    let stdout = self.child.stdout.as_mut().ok_or("Failed to get stdout")?;
-    let mut reader = BufReader::new(stdout);
-    let mut response_line = String::new();
-    reader.read_line(&mut response_line).await?;
+  // Initialize result. rmcp runs the handshake while connecting, so this
+  // only hands back what the server reported.
+  #[deprecated(note = "the handshake runs while connecting; use `server()`")]
+  pub async fn initialize(&self) -> Result<InitializeResult, Box<dyn std::error::Error>> {
+    Ok(self.server.clone())
+  }
+
+  pub fn server(&self) -> &InitializeResult {
+    &self.server
+  }
 
-    let response: Value = serde_json::from_str(&response_line)?;
+  // Fail early when the server did not advertise `capability` in initialize
+  // Whether the server announced `capability` (tools, resources, prompts, ...)
+  pub fn supports(&self, capability: &str) -> bool {
+    self.server.capabilities.get(capability).is_some()
+  }
 
-    if let Some(error) = response.get("error") {
-      return Err(format!("MCP Server Error: {}", error).into());
+  fn require_capability(&self, capability: &str) -> Result<(), Box<dyn std::error::Error>> {
+    if !self.supports(capability) {
+      return Err(format!("MCP server does not support {}", capability).into());
     }
+    Ok(())
+  }
 
-    Ok(response["result"].clone())
+  // Run a paginated list request, following nextCursor until the last page,
+  // and collect the items under `key` from every page
+  async fn list_all(
+    &self,
+    method: &str,
+    key: &str,
+  ) -> Result<Vec<Value>, Box<dyn std::error::Error>> {
+    let mut items = Vec::new();
+    let mut cursor: Option<String> = None;
+    // Every cursor followed so far; a server cycling through cursors would
+    // never finish
+    let mut seen = HashSet::new();
+    loop {
+      let params = match &cursor {
+        Some(cursor) => json!({ "cursor": cursor }),
+        None => json!({}),
+      };
+      let mut result = self.send_request(method, params).await?;
+      if let Some(page) = result[key].as_array_mut() {
+        items.append(page);
+      }
+
+      match result["nextCursor"].as_str() {
+        Some(next) if !seen.insert(next.to_string()) => {
+          return Err(format!("MCP server repeated cursor `{}` for {}", next, method).into());
+        }
+        Some(next) => cursor = Some(next.to_string()),
+        None => return Ok(items),
+      }
+    }
   }
 
-  pub async fn initialize(&mut self) -> Result<Value, Box<dyn std::error::Error>> {
+  pub async fn list_tools(&self) -> Result<Vec<Value>, Box<dyn std::error::Error>> {
+    self.require_capability("tools")?;
+    // Read first so a change announced while listing is not lost, and only
+    // marked as seen once the listing succeeded
+    let generation = self.handlers.lock().unwrap().tools_generation;
+    let tools = self.list_all("tools/list", "tools").await?;
+
+    *self.tool_schemas.lock().unwrap() = tools
+      .iter()
+      .filter_map(|tool| {
+        Some((
+          tool["name"].as_str()?.to_string(),
+          tool["inputSchema"].clone(),
+        ))
+      })
+      .collect();
+    self
+      .listed_tools_generation
+      .fetch_max(generation, Ordering::SeqCst);
+    Ok(tools)
+  }
+
+  // Whether the server announced a changed tool list since the last
+  // list_tools, so tools listed before may be gone or new ones available
+  pub fn tools_changed(&self) -> bool {
+    let generation = self.handlers.lock().unwrap().tools_generation;
+    generation != self.listed_tools_generation.load(Ordering::SeqCst)
+  }
+
+  // Check arguments against the tool's inputSchema from the last tools/list.
+  // Tools that have not been listed yet are not checked.
+  pub fn validate_tool_arguments(
+    &self,
+    name: &str,
+    arguments: &Value,
+  ) -> Result<(), InvalidArguments> {
+    match self.tool_schemas.lock().unwrap().get(name) {
+      Some(schema) => validate_arguments(name, schema, arguments),
+      None => Ok(()),
+    }
+  }
+
+  pub async fn call_tool(
+    &self,
+    name: &str,
+    arguments: Value,
+  ) -> Result<Value, Box<dyn std::error::Error>> {
+    self.require_capability("tools")?;
+    self.validate_tool_arguments(name, &arguments)?;
     self
       .send_request(
-        "initialize",
+        "tools/call",
         json!({
-          "protocolVersion": "2024-11-05",
-          "capabilities": {}
+          "name": name,
+          "arguments": arguments
         }),
       )
       .await
   }
 
-  pub async fn list_tools(&mut self) -> Result<Vec<Value>, Box<dyn std::error::Error>> {
-    let result = self.send_request("tools/list", json!({})).await?;
-    Ok(result["tools"].as_array().unwrap_or(&vec![]).clone())
-  }
-
-  pub async fn call_tool(
-    &mut self,
+  // call_tool that asks the server for progress updates and passes each one
+  // to `on_progress` while the call is running
+  pub async fn call_tool_with_progress(
+    &self,
     name: &str,
     arguments: Value,
+    on_progress: impl Fn(Progress) + Send + Sync + 'static,
   ) -> Result<Value, Box<dyn std::error::Error>> {
+    self.require_capability("tools")?;
+    self.validate_tool_arguments(name, &arguments)?;
+
+    let token = format!(
+      "progress-{}",
+      self.progress_token.fetch_add(1, Ordering::Relaxed) + 1
+    );
+    let _guard = ProgressGuard {
+      token: token.clone(),
+      handlers: self.handlers.clone(),
+    };
     self
-      .send_request(
+      .handlers
+      .lock()
+      .unwrap()
+      .progress
+      .insert(token.clone(), Arc::new(on_progress));
+
+    let meta = Meta::with_progress_token(ProgressToken(NumberOrString::String(token.into())));
+    self
+      .request(
         "tools/call",
         json!({
           "name": name,
           "arguments": arguments
         }),
+        Some(meta),
       )
       .await
   }
 
-  #[allow(dead_code)]
-  pub async fn list_resources(&mut self) -> Result<Vec<Value>, Box<dyn std::error::Error>> {
-    let result = self.send_request("resources/list", json!({})).await?;
-    Ok(result["resources"].as_array().unwrap_or(&vec![]).clone())
+  pub async fn list_resources(&self) -> Result<Vec<Value>, Box<dyn std::error::Error>> {
+    self.require_capability("resources")?;
+    self.list_all("resources/list", "resources").await
   }
 
-  #[allow(dead_code)]
-  pub async fn read_resource(&mut self, uri: &str) -> Result<Value, Box<dyn std::error::Error>> {
+  pub async fn list_resource_templates(&self) -> Result<Vec<Value>, Box<dyn std::error::Error>> {
+    self.require_capability("resources")?;
+    self
+      .list_all("resources/templates/list", "resourceTemplates")
+      .await
+  }
+
+  // Ask the server for notifications/resources/updated whenever `uri`
+  // changes; they arrive on the resource_updates streams
+  pub async fn subscribe_resource(&self, uri: &str) -> Result<(), Box<dyn std::error::Error>> {
+    self.require_resource_subscriptions()?;
+    self
+      .send_request("resources/subscribe", json!({ "uri": uri }))
+      .await?;
+    Ok(())
+  }
+
+  pub async fn unsubscribe_resource(&self, uri: &str) -> Result<(), Box<dyn std::error::Error>> {
+    self.require_resource_subscriptions()?;
+    self
+      .send_request("resources/unsubscribe", json!({ "uri": uri }))
+      .await?;
+    Ok(())
+  }
+
+  // Stream of the URIs of updated subscribed resources. Every stream sees
+  // every update; drop the receiver to stop listening.
+  pub fn resource_updates(&self) -> mpsc::UnboundedReceiver<String> {
+    let (sender, receiver) = mpsc::unbounded_channel();
+    self.handlers.lock().unwrap().resource_updates.push(sender);
+    receiver
+  }
+
+  fn require_resource_subscriptions(&self) -> Result<(), Box<dyn std::error::Error>> {
+    if self.server.capabilities["resources"]["subscribe"] != true {
+      return Err("MCP server does not support resource subscriptions".into());
+    }
+    Ok(())
+  }
+
+  pub async fn read_resource(&self, uri: &str) -> Result<Value, Box<dyn std::error::Error>> {
+    self.require_capability("resources")?;
     self
       .send_request(
         "resources/read",
@@ -119,18 +672,17 @@ impl McpClient {
       .await
   }
 
-  #[allow(dead_code)]
-  pub async fn list_prompts(&mut self) -> Result<Vec<Value>, Box<dyn std::error::Error>> {
-    let result = self.send_request("prompts/list", json!({})).await?;
-    Ok(result["prompts"].as_array().unwrap_or(&vec![]).clone())
+  pub async fn list_prompts(&self) -> Result<Vec<Value>, Box<dyn std::error::Error>> {
+    self.require_capability("prompts")?;
+    self.list_all("prompts/list", "prompts").await
   }
 
-  #[allow(dead_code)]
   pub async fn get_prompt(
-    &mut self,
+    &self,
     name: &str,
     arguments: Value,
   ) -> Result<Value, Box<dyn std::error::Error>> {
+    self.require_capability("prompts")?;
     self
       .send_request(
         "prompts/get",
@@ -143,14 +695,17 @@ impl McpClient {
   }
 }
 
-impl Drop for McpClient {
-  fn drop(&mut self) {
-    // Use start() to spawn the future without awaiting it
-    tokio::spawn(async move {
-      // This will terminate the process when the client is dropped
-    });
+// Error responses surface as JsonRpcError, like the ones the server sends
+fn request_error(error: ServiceError) -> Box<dyn std::error::Error> {
+  match error {
+    ServiceError::McpError(error) => Box::new(JsonRpcError::from(error)),
+    error => Box::new(error),
+  }
+}
 
-    // Force kill the child process
-    let _ = self.child.start_kill();
+fn token_key(token: &ProgressToken) -> String {
+  match &token.0 {
+    NumberOrString::String(token) => token.to_string(),
+    NumberOrString::Number(token) => token.to_string(),
   }
 }
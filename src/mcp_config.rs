@@ -0,0 +1,235 @@
+use serde::{Deserialize, Serialize};
+use std::collections::{BTreeMap, HashMap};
+use std::env;
+use std::ffi::OsString;
+use std::fs;
+use std::path::{Path, PathBuf};
+
+use crate::mcp_client::ServerCommand;
+use crate::mcp_host::check_server_name_format;
+
+pub const MCP_CONFIG_FLAG: &str = "--mcp-config";
+pub const MCP_CONFIG_ENV: &str = "MCP_CONFIG";
+
+// `{"mcpServers": {"name": {"command", "args", "env"}}}` as used by most MCP hosts;
+// a server reachable over Streamable HTTP is given as `{"url": ...}` instead
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct McpConfig {
+  #[serde(rename = "mcpServers")]
+  pub mcp_servers: BTreeMap<String, McpServerConfig>,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct McpServerConfig {
+  #[serde(default, skip_serializing_if = "String::is_empty")]
+  pub command: String,
+  #[serde(default)]
+  pub args: Vec<String>,
+  #[serde(default)]
+  pub env: HashMap<String, String>,
+  #[serde(default, skip_serializing_if = "Option::is_none")]
+  pub cwd: Option<PathBuf>,
+  #[serde(default, skip_serializing_if = "Option::is_none")]
+  pub url: Option<String>,
+}
+
+impl McpServerConfig {
+  pub fn to_server_command(&self) -> ServerCommand {
+    ServerCommand {
+      program: self.command.clone(),
+      args: self.args.clone(),
+      env: self.env.clone(),
+      cwd: self.cwd.clone(),
+    }
+  }
+}
+
+impl McpConfig {
+  pub fn parse(json: &str) -> Result<Self, Box<dyn std::error::Error>> {
+    let config: McpConfig =
+      serde_json::from_str(json).map_err(|e| format!("Invalid MCP config: {}", e))?;
+    config.validate()?;
+    Ok(config)
+  }
+
+  pub fn from_file(path: impl AsRef<Path>) -> Result<Self, Box<dyn std::error::Error>> {
+    let path = path.as_ref();
+    let json = fs::read_to_string(path)
+      .map_err(|e| format!("Failed to read MCP config {}: {}", path.display(), e))?;
+    Self::parse(&json).map_err(|e| format!("{}: {}", path.display(), e).into())
+  }
+
+  pub fn validate(&self) -> Result<(), Box<dyn std::error::Error>> {
+    if self.mcp_servers.is_empty() {
+      return Err("Invalid MCP config: `mcpServers` is empty".into());
+    }
+
+    let mut problems = Vec::new();
+    for (name, server) in &self.mcp_servers {
+      if let Err(problem) = check_server_name_format(name) {
+        problems.push(problem);
+      }
+      match (server.command.trim().is_empty(), &server.url) {
+        (true, None) => problems.push(format!("server `{}` needs a `command` or a `url`", name)),
+        (false, Some(_)) => problems.push(format!(
+          "server `{}` must not have both `command` and `url`",
+          name
+        )),
+        (true, Some(url)) if !url.starts_with("http://") && !url.starts_with("https://") => {
+          problems.push(format!("server `{}` has an invalid `url`: {}", name, url))
+        }
+        _ => {}
+      }
+    }
+
+    if problems.is_empty() {
+      Ok(())
+    } else {
+      Err(format!("Invalid MCP config: {}", problems.join("; ")).into())
+    }
+  }
+
+  // Config path from `--mcp-config <path>` (or `--mcp-config=<path>`) on the
+  // command line, falling back to the MCP_CONFIG environment variable
+  pub fn path_from_args_or_env() -> Result<Option<PathBuf>, Box<dyn std::error::Error>> {
+    Ok(path_from(env::args().skip(1), env::var_os(MCP_CONFIG_ENV))?)
+  }
+}
+
+// A flag without a path is an error rather than a reason to fall back to
+// the environment
+fn path_from(
+  args: impl IntoIterator<Item = String>,
+  env_value: Option<OsString>,
+) -> Result<Option<PathBuf>, String> {
+  let mut args = args.into_iter();
+  while let Some(arg) = args.next() {
+    let path = if arg == MCP_CONFIG_FLAG {
+      args.next()
+    } else if let Some(path) = arg
+      .strip_prefix(MCP_CONFIG_FLAG)
+      .and_then(|rest| rest.strip_prefix('='))
+    {
+      Some(path.to_string())
+    } else {
+      continue;
+    };
+    return match path {
+      Some(path) if !path.is_empty() => Ok(Some(PathBuf::from(path))),
+      _ => Err(format!("{} needs a path", MCP_CONFIG_FLAG)),
+    };
+  }
+  Ok(env_value.map(PathBuf::from))
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+
+  fn args(args: &[&str]) -> Vec<String> {
+    args.iter().map(|arg| arg.to_string()).collect()
+  }
+
+  // Problems reported by validate, or "" when the config is fine
+  fn problems(json: &str) -> String {
+    match McpConfig::parse(json) {
+      Ok(_) => String::new(),
+      Err(e) => e.to_string(),
+    }
+  }
+
+  #[test]
+  fn path_comes_from_the_flag_before_the_environment() {
+    let env_value = Some(OsString::from("env.json"));
+    assert_eq!(
+      path_from(args(&["--mcp-config", "a.json"]), env_value.clone()),
+      Ok(Some(PathBuf::from("a.json")))
+    );
+    assert_eq!(
+      path_from(args(&["query", "--mcp-config=b.json"]), env_value.clone()),
+      Ok(Some(PathBuf::from("b.json")))
+    );
+    assert_eq!(
+      path_from(args(&["query"]), env_value),
+      Ok(Some(PathBuf::from("env.json")))
+    );
+    assert_eq!(path_from(args(&["query"]), None), Ok(None));
+  }
+
+  #[test]
+  fn flag_without_a_path_is_an_error() {
+    let env_value = Some(OsString::from("env.json"));
+    for flag in [&["--mcp-config"][..], &["query", "--mcp-config="]] {
+      assert_eq!(
+        path_from(args(flag), env_value.clone()),
+        Err("--mcp-config needs a path".to_string())
+      );
+    }
+  }
+
+  #[test]
+  fn valid_configs_parse() {
+    let config = McpConfig::parse(
+      r#"{"mcpServers": {
+        "local": {"command": "mcp_server", "args": ["--stdio"]},
+        "remote_2": {"url": "https://example.com/mcp"}
+      }}"#,
+    )
+    .unwrap();
+    assert_eq!(config.mcp_servers["local"].args, ["--stdio"]);
+    assert_eq!(
+      config.mcp_servers["remote_2"].url.as_deref(),
+      Some("https://example.com/mcp")
+    );
+  }
+
+  #[test]
+  fn empty_server_list_is_rejected() {
+    assert_eq!(
+      problems(r#"{"mcpServers": {}}"#),
+      "Invalid MCP config: `mcpServers` is empty"
+    );
+  }
+
+  #[test]
+  fn servers_need_exactly_one_of_command_and_url() {
+    assert_eq!(
+      problems(r#"{"mcpServers": {"a": {"command": "x", "url": "http://localhost/mcp"}}}"#),
+      "Invalid MCP config: server `a` must not have both `command` and `url`"
+    );
+    assert_eq!(
+      problems(r#"{"mcpServers": {"a": {"args": ["x"]}}}"#),
+      "Invalid MCP config: server `a` needs a `command` or a `url`"
+    );
+  }
+
+  #[test]
+  fn urls_must_be_http() {
+    assert_eq!(
+      problems(r#"{"mcpServers": {"a": {"url": "ftp://localhost/mcp"}}}"#),
+      "Invalid MCP config: server `a` has an invalid `url`: ftp://localhost/mcp"
+    );
+  }
+
+  #[test]
+  fn server_names_must_be_valid_function_name_prefixes() {
+    for name in ["", "a__b", "weather.v2", "天气"] {
+      let json = format!(r#"{{"mcpServers": {{"{}": {{"command": "x"}}}}}}"#, name);
+      assert!(
+        problems(&json).contains(&format!("server name `{}` must be", name)),
+        "{}",
+        problems(&json)
+      );
+    }
+  }
+
+  #[test]
+  fn all_problems_are_reported_together() {
+    assert_eq!(
+      problems(r#"{"mcpServers": {"a.b": {}, "c": {"command": "x", "url": "http://c"}}}"#),
+      "Invalid MCP config: server name `a.b` must be 1-64 letters, digits, `_` or `-` \
+       and must not contain `__`; server `a.b` needs a `command` or a `url`; \
+       server `c` must not have both `command` and `url`"
+    );
+  }
+}
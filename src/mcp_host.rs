@@ -0,0 +1,307 @@
+use serde_json::Value;
+use std::collections::HashMap;
+use tracing::info;
+
+use crate::mcp_client::{McpClient, Progress, ServerCommand};
+use crate::mcp_config::McpConfig;
+
+// Separator between server name and tool name for namespaced tools
+pub const TOOL_NAME_SEPARATOR: &str = "__";
+
+// DeepSeek function names are 1-64 letters, digits, `_` or `-`
+pub const MAX_TOOL_NAME_LEN: usize = 64;
+
+fn is_tool_name_char(c: char) -> bool {
+  c.is_ascii_alphanumeric() || c == '_' || c == '-'
+}
+
+// Server names end up in exposed tool names, so they are held to the same
+// characters and must not contain the separator
+pub fn check_server_name_format(name: &str) -> Result<(), String> {
+  if name.is_empty()
+    || name.len() > MAX_TOOL_NAME_LEN
+    || !name.chars().all(is_tool_name_char)
+    || name.contains(TOOL_NAME_SEPARATOR)
+  {
+    return Err(format!(
+      "server name `{}` must be 1-{} letters, digits, `_` or `-` and must not contain `{}`",
+      name, MAX_TOOL_NAME_LEN, TOOL_NAME_SEPARATOR
+    ));
+  }
+  Ok(())
+}
+
+// Tool name as offered to the LLM: other characters become `_` and the
+// result is cut to the maximum length
+fn sanitize_tool_name(name: &str) -> String {
+  let name: String = name
+    .chars()
+    .map(|c| if is_tool_name_char(c) { c } else { '_' })
+    .take(MAX_TOOL_NAME_LEN)
+    .collect();
+  if name.is_empty() {
+    "_".to_string()
+  } else {
+    name
+  }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+struct ToolRoute {
+  server: String,
+  tool: String,
+}
+
+// A server's tool while the merged list is built
+struct ExposedTool {
+  server: String,
+  tool: String,
+  definition: Value,
+  prefixed: bool,
+}
+
+impl ExposedTool {
+  fn exposed_name(&self) -> String {
+    if self.prefixed {
+      sanitize_tool_name(&format!(
+        "{}{}{}",
+        self.server, TOOL_NAME_SEPARATOR, self.tool
+      ))
+    } else {
+      sanitize_tool_name(&self.tool)
+    }
+  }
+}
+
+// Host over several MCP servers. Tools of all servers are merged into one
+// list; a tool name offered by more than one server is exposed as
+// `server__tool` for every server that has it, unique names stay unchanged.
+// Exposed names are always unique and valid DeepSeek function names.
+#[derive(Default)]
+pub struct McpHost {
+  servers: Vec<(String, McpClient)>,
+  tools: Vec<Value>,
+  routes: HashMap<String, ToolRoute>,
+}
+
+impl McpHost {
+  pub fn new() -> Self {
+    Self::default()
+  }
+
+  // Start and initialize every server listed in the config
+  pub async fn from_config(config: &McpConfig) -> Result<Self, Box<dyn std::error::Error>> {
+    let mut host = McpHost::new();
+    for (name, server) in &config.mcp_servers {
+      let connected = match &server.url {
+        Some(url) => host.connect_http(name, url).await,
+        None => host.connect(name, server.to_server_command()).await,
+      };
+      connected.map_err(|e| format!("MCP server `{}`: {}", name, e))?;
+    }
+    Ok(host)
+  }
+
+  // Start a server, run the initialize handshake and add it to the host
+  pub async fn connect(
+    &mut self,
+    name: &str,
+    command: ServerCommand,
+  ) -> Result<(), Box<dyn std::error::Error>> {
+    self.check_server_name(name)?;
+
+    let client = McpClient::new(command).await?;
+    self.add_client(name, client).await
+  }
+
+  // Same as connect for a server already running behind a Streamable HTTP endpoint
+  pub async fn connect_http(
+    &mut self,
+    name: &str,
+    url: &str,
+  ) -> Result<(), Box<dyn std::error::Error>> {
+    self.check_server_name(name)?;
+
+    let client = McpClient::connect_http(url).await?;
+    self.add_client(name, client).await
+  }
+
+  // Add an already initialized client
+  pub async fn add_client(
+    &mut self,
+    name: &str,
+    client: McpClient,
+  ) -> Result<(), Box<dyn std::error::Error>> {
+    self.check_server_name(name)?;
+
+    info!("MCP Host: Adding server {}", name);
+    self.servers.push((name.to_string(), client));
+    // A server whose tools can't be listed is not added
+    if let Err(e) = self.refresh_tools().await {
+      self.servers.pop();
+      return Err(e);
+    }
+    Ok(())
+  }
+
+  fn check_server_name(&self, name: &str) -> Result<(), Box<dyn std::error::Error>> {
+    check_server_name_format(name).map_err(|e| format!("Invalid MCP server name: {}", e))?;
+    if self.servers.iter().any(|(existing, _)| existing == name) {
+      return Err(format!("MCP server `{}` is already connected", name).into());
+    }
+    Ok(())
+  }
+
+  // Re-run tools/list on every server offering tools and rebuild the merged
+  // tool list
+  pub async fn refresh_tools(&mut self) -> Result<(), Box<dyn std::error::Error>> {
+    let mut per_server = Vec::with_capacity(self.servers.len());
+    for (name, client) in &self.servers {
+      if !client.supports("tools") {
+        continue;
+      }
+      per_server.push((name.clone(), client.list_tools().await?));
+    }
+
+    // Exposed names start out as the sanitized tool names. Every name shared
+    // by more than one tool is prefixed with its server; as a prefixed name
+    // can in turn clash with a plain one (`b__x` of server a and `x` of
+    // server b), this repeats until nothing changes. Prefixed names of
+    // different servers only clash when cut to the maximum length, since
+    // server names can't contain the separator.
+    let mut candidates: Vec<ExposedTool> = per_server
+      .into_iter()
+      .flat_map(|(server, tools)| {
+        tools.into_iter().map(move |definition| ExposedTool {
+          server: server.clone(),
+          tool: definition["name"].as_str().unwrap_or_default().to_string(),
+          definition,
+          prefixed: false,
+        })
+      })
+      .collect();
+    loop {
+      let mut name_counts: HashMap<String, usize> = HashMap::new();
+      for candidate in &candidates {
+        *name_counts.entry(candidate.exposed_name()).or_default() += 1;
+      }
+      let mut changed = false;
+      for candidate in candidates.iter_mut() {
+        if !candidate.prefixed && name_counts[&candidate.exposed_name()] > 1 {
+          candidate.prefixed = true;
+          changed = true;
+        }
+      }
+      if !changed {
+        break;
+      }
+    }
+
+    let mut merged = Vec::new();
+    let mut routes = HashMap::new();
+    for candidate in candidates {
+      let exposed = candidate.exposed_name();
+      let ExposedTool {
+        server,
+        tool,
+        mut definition,
+        ..
+      } = candidate;
+      // Only left when a server lists the same tool twice, or two names
+      // only differ in characters or length a function name can't have
+      if let Some(existing) = routes.get(&exposed) {
+        let ToolRoute {
+          server: existing_server,
+          tool: existing_tool,
+        } = existing;
+        return Err(
+          format!(
+            "MCP tools `{}` of `{}` and `{}` of `{}` would both be exposed as `{}`",
+            existing_tool, existing_server, tool, server, exposed
+          )
+          .into(),
+        );
+      }
+
+      definition["name"] = Value::String(exposed.clone());
+      routes.insert(exposed, ToolRoute { server, tool });
+      merged.push(definition);
+    }
+
+    info!(
+      "MCP Host: {} tool(s) from {} server(s)",
+      merged.len(),
+      self.servers.len()
+    );
+    self.tools = merged;
+    self.routes = routes;
+    Ok(())
+  }
+
+  // Rebuild the merged tool list when a server announced that its tools
+  // changed. Returns whether the list was refreshed.
+  pub async fn refresh_tools_if_changed(&mut self) -> Result<bool, Box<dyn std::error::Error>> {
+    if !self
+      .servers
+      .iter()
+      .any(|(_, client)| client.tools_changed())
+    {
+      return Ok(false);
+    }
+    info!("MCP Host: Tool list changed, refreshing");
+    self.refresh_tools().await?;
+    Ok(true)
+  }
+
+  // Merged tools/list entries, with names as exposed to the LLM
+  pub fn list_tools(&self) -> &[Value] {
+    &self.tools
+  }
+
+  pub fn server_names(&self) -> impl Iterator<Item = &str> {
+    self.servers.iter().map(|(name, _)| name.as_str())
+  }
+
+  pub fn client(&self, server: &str) -> Option<&McpClient> {
+    self
+      .servers
+      .iter()
+      .find(|(name, _)| name == server)
+      .map(|(_, client)| client)
+  }
+
+  // Route a tool call by its exposed name to the server that owns the tool
+  pub async fn call_tool(
+    &self,
+    name: &str,
+    arguments: Value,
+  ) -> Result<Value, Box<dyn std::error::Error>> {
+    let (client, tool) = self.route(name)?;
+    client.call_tool(tool, arguments).await
+  }
+
+  // call_tool with progress updates from the owning server
+  pub async fn call_tool_with_progress(
+    &self,
+    name: &str,
+    arguments: Value,
+    on_progress: impl Fn(Progress) + Send + Sync + 'static,
+  ) -> Result<Value, Box<dyn std::error::Error>> {
+    let (client, tool) = self.route(name)?;
+    client
+      .call_tool_with_progress(tool, arguments, on_progress)
+      .await
+  }
+
+  fn route(&self, name: &str) -> Result<(&McpClient, &str), Box<dyn std::error::Error>> {
+    let route = self
+      .routes
+      .get(name)
+      .ok_or_else(|| format!("Unknown tool: {}", name))?;
+
+    let client = self
+      .client(&route.server)
+      .ok_or_else(|| format!("MCP server `{}` is not connected", route.server))?;
+    Ok((client, &route.tool))
+  }
+}
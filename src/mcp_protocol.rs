@@ -0,0 +1,146 @@
+// Protocol details shared by the MCP server and client
+
+use rmcp::model::{ErrorCode, ErrorData};
+use serde_json::{Value, json};
+use std::fmt;
+
+use crate::mcp_schema::InvalidArguments;
+
+pub const LATEST_PROTOCOL_VERSION: &str = "2025-06-18";
+
+// Newest first; the server answers with the client's version when it is listed
+pub const SUPPORTED_PROTOCOL_VERSIONS: &[&str] = &["2025-06-18", "2025-03-26", "2024-11-05"];
+
+pub fn is_supported_version(version: &str) -> bool {
+  SUPPORTED_PROTOCOL_VERSIONS.contains(&version)
+}
+
+// Version the server should reply with for the client's requested version
+pub fn negotiate_version(requested: Option<&str>) -> &'static str {
+  requested
+    .and_then(|requested| {
+      SUPPORTED_PROTOCOL_VERSIONS
+        .iter()
+        .find(|&&supported| supported == requested)
+    })
+    .copied()
+    .unwrap_or(LATEST_PROTOCOL_VERSION)
+}
+
+// Standard JSON-RPC 2.0 error codes
+pub const PARSE_ERROR: i64 = -32700;
+pub const INVALID_REQUEST: i64 = -32600;
+pub const METHOD_NOT_FOUND: i64 = -32601;
+pub const INVALID_PARAMS: i64 = -32602;
+pub const INTERNAL_ERROR: i64 = -32603;
+
+// Not a JSON-RPC code: the one LSP uses for cancelled requests. Handlers end
+// with it once the client sent notifications/cancelled; the stdio transport
+// drops such responses, the HTTP one still delivers them.
+pub const REQUEST_CANCELLED: i64 = -32800;
+
+// JSON-RPC error object, what the server answers a failed request with and
+// what the client hands back to its caller
+#[derive(Debug, Clone, PartialEq)]
+pub struct JsonRpcError {
+  pub code: i64,
+  pub message: String,
+  pub data: Option<Value>,
+}
+
+impl JsonRpcError {
+  pub fn new(code: i64, message: impl Into<String>) -> Self {
+    Self {
+      code,
+      message: message.into(),
+      data: None,
+    }
+  }
+
+  pub fn parse_error(message: impl fmt::Display) -> Self {
+    Self::new(PARSE_ERROR, format!("Parse error: {}", message))
+  }
+
+  pub fn invalid_request(message: impl Into<String>) -> Self {
+    Self::new(INVALID_REQUEST, message)
+  }
+
+  pub fn method_not_found(method: &str) -> Self {
+    Self::new(METHOD_NOT_FOUND, format!("Method not found: {}", method))
+  }
+
+  pub fn invalid_params(message: impl Into<String>) -> Self {
+    Self::new(INVALID_PARAMS, message)
+  }
+
+  pub fn internal_error(message: impl Into<String>) -> Self {
+    Self::new(INTERNAL_ERROR, message)
+  }
+
+  pub fn with_data(mut self, data: Value) -> Self {
+    self.data = Some(data);
+    self
+  }
+
+  pub fn to_value(&self) -> Value {
+    let mut error = json!({
+      "code": self.code,
+      "message": self.message
+    });
+    if let Some(data) = &self.data {
+      error["data"] = data.clone();
+    }
+    error
+  }
+
+  // Complete error response for the request `id` (null when it is unknown)
+  pub fn to_response(&self, id: Value) -> Value {
+    json!({
+      "jsonrpc": "2.0",
+      "id": id,
+      "error": self.to_value()
+    })
+  }
+}
+
+impl fmt::Display for JsonRpcError {
+  fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+    write!(f, "JSON-RPC error {}: {}", self.code, self.message)
+  }
+}
+
+impl std::error::Error for JsonRpcError {}
+
+impl From<InvalidArguments> for JsonRpcError {
+  fn from(invalid: InvalidArguments) -> Self {
+    Self::invalid_params(invalid.to_string())
+      .with_data(json!({ "violations": invalid.to_error_data() }))
+  }
+}
+
+// rmcp carries the same error object as ErrorData
+impl From<ErrorData> for JsonRpcError {
+  fn from(error: ErrorData) -> Self {
+    Self {
+      code: error.code.0.into(),
+      message: error.message.into_owned(),
+      data: error.data,
+    }
+  }
+}
+
+impl From<JsonRpcError> for ErrorData {
+  fn from(error: JsonRpcError) -> Self {
+    let code = i32::try_from(error.code).unwrap_or(ErrorCode::INTERNAL_ERROR.0);
+    ErrorData::new(ErrorCode(code), error.message, error.data)
+  }
+}
+
+// Successful response for the request `id`
+pub fn result_response(id: Value, result: Value) -> Value {
+  json!({
+    "jsonrpc": "2.0",
+    "id": id,
+    "result": result
+  })
+}
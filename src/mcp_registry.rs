@@ -0,0 +1,536 @@
+use async_trait::async_trait;
+use percent_encoding::percent_decode_str;
+use serde_json::{Value, json};
+use std::collections::HashMap;
+use std::sync::{Arc, RwLock};
+use tokio::sync::broadcast;
+
+pub type BoxError = Box<dyn std::error::Error + Send + Sync>;
+
+// `?` has no conversion from BoxError to the plain Box<dyn Error> used elsewhere
+pub fn unsend(error: BoxError) -> Box<dyn std::error::Error> {
+  error
+}
+
+// Text result of a tool execution. `is_error` marks failures of the
+// underlying API, which are reported to the model rather than as protocol errors.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ToolOutput {
+  pub text: String,
+  pub is_error: bool,
+}
+
+impl ToolOutput {
+  pub fn text(text: impl Into<String>) -> Self {
+    Self {
+      text: text.into(),
+      is_error: false,
+    }
+  }
+
+  pub fn error(text: impl Into<String>) -> Self {
+    Self {
+      text: text.into(),
+      is_error: true,
+    }
+  }
+
+  // MCP tools/call result
+  pub fn to_call_result(&self) -> Value {
+    json!({
+      "content": [{
+        "type": "text",
+        "text": self.text
+      }],
+      "isError": self.is_error
+    })
+  }
+}
+
+// Sends one notifications/progress message for the call being executed
+pub type ProgressSink = Arc<dyn Fn(Value) + Send + Sync>;
+
+// Per-call handle passed to Tool::execute. Progress is only reported when
+// the caller asked for it with `_meta.progressToken`; otherwise it is a no-op.
+#[derive(Clone, Default)]
+pub struct ToolContext {
+  progress: Option<(Value, ProgressSink)>,
+}
+
+impl ToolContext {
+  pub fn new() -> Self {
+    Self::default()
+  }
+
+  pub fn with_progress(token: Value, sink: ProgressSink) -> Self {
+    Self {
+      progress: Some((token, sink)),
+    }
+  }
+
+  // Whether the caller is listening for progress
+  pub fn wants_progress(&self) -> bool {
+    self.progress.is_some()
+  }
+
+  // Report progress; `progress` must increase with every call
+  pub fn progress(&self, progress: f64, total: Option<f64>, message: Option<&str>) {
+    let Some((token, sink)) = &self.progress else {
+      return;
+    };
+
+    let mut params = json!({
+      "progressToken": token,
+      "progress": progress
+    });
+    if let Some(total) = total {
+      params["total"] = json!(total);
+    }
+    if let Some(message) = message {
+      params["message"] = json!(message);
+    }
+    sink(json!({
+      "jsonrpc": "2.0",
+      "method": "notifications/progress",
+      "params": params
+    }));
+  }
+}
+
+#[async_trait]
+pub trait Tool: Send + Sync {
+  fn name(&self) -> &str;
+
+  fn description(&self) -> &str;
+
+  // JSON Schema of the `arguments` object
+  fn input_schema(&self) -> Value;
+
+  // System prompt suited to answering from this tool's output
+  fn system_prompt(&self) -> Option<&str> {
+    None
+  }
+
+  async fn execute(&self, arguments: Value, context: ToolContext) -> Result<ToolOutput, BoxError>;
+
+  // MCP tools/list entry
+  fn definition(&self) -> Value {
+    json!({
+      "name": self.name(),
+      "description": self.description(),
+      "inputSchema": self.input_schema()
+    })
+  }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct PromptArgument {
+  pub name: String,
+  pub description: String,
+  pub required: bool,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct PromptMessage {
+  pub role: String,
+  pub text: String,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct PromptResult {
+  pub description: String,
+  pub messages: Vec<PromptMessage>,
+}
+
+impl PromptResult {
+  // MCP prompts/get result
+  pub fn to_get_result(&self) -> Value {
+    json!({
+      "description": self.description,
+      "messages": self.messages.iter().map(|message| json!({
+        "role": message.role,
+        "content": {
+          "type": "text",
+          "text": message.text
+        }
+      })).collect::<Vec<_>>()
+    })
+  }
+}
+
+pub trait Prompt: Send + Sync {
+  fn name(&self) -> &str;
+
+  fn description(&self) -> &str;
+
+  fn arguments(&self) -> Vec<PromptArgument>;
+
+  fn get(&self, arguments: &Value) -> Result<PromptResult, BoxError>;
+
+  // MCP prompts/list entry
+  fn definition(&self) -> Value {
+    json!({
+      "name": self.name(),
+      "description": self.description(),
+      "arguments": self.arguments().iter().map(|argument| json!({
+        "name": argument.name,
+        "description": argument.description,
+        "required": argument.required
+      })).collect::<Vec<_>>()
+    })
+  }
+}
+
+#[async_trait]
+pub trait Resource: Send + Sync {
+  fn uri(&self) -> &str;
+
+  fn name(&self) -> &str;
+
+  fn description(&self) -> &str;
+
+  fn mime_type(&self) -> &str {
+    "application/json"
+  }
+
+  async fn read(&self) -> Result<String, BoxError>;
+
+  // MCP resources/list entry
+  fn definition(&self) -> Value {
+    json!({
+      "uri": self.uri(),
+      "name": self.name(),
+      "description": self.description(),
+      "mimeType": self.mime_type()
+    })
+  }
+}
+
+// Values of URI template variables, by variable name
+pub type TemplateArguments = HashMap<String, String>;
+
+// Family of resources addressed by an RFC 6570 URI template such as
+// `weather://forecast/{city}`. Reads get the template variables taken from
+// the requested URI, percent-decoded.
+#[async_trait]
+pub trait ResourceTemplate: Send + Sync {
+  fn uri_template(&self) -> &str;
+
+  fn name(&self) -> &str;
+
+  fn description(&self) -> &str;
+
+  fn mime_type(&self) -> &str {
+    "application/json"
+  }
+
+  async fn read(&self, arguments: TemplateArguments) -> Result<String, BoxError>;
+
+  // MCP resources/templates/list entry
+  fn definition(&self) -> Value {
+    json!({
+      "uriTemplate": self.uri_template(),
+      "name": self.name(),
+      "description": self.description(),
+      "mimeType": self.mime_type()
+    })
+  }
+}
+
+// Match `uri` against a template made of literal text and `{name}`
+// variables. A variable takes everything up to the next literal text and
+// never spans a `/`.
+pub fn match_uri_template(template: &str, uri: &str) -> Option<TemplateArguments> {
+  let mut arguments = HashMap::new();
+  let mut template = template;
+  let mut uri = uri;
+  while !template.is_empty() {
+    let Some(rest) = template.strip_prefix('{') else {
+      let literal = &template[..template.find('{').unwrap_or(template.len())];
+      uri = uri.strip_prefix(literal)?;
+      template = &template[literal.len()..];
+      continue;
+    };
+
+    let (name, rest) = rest.split_once('}')?;
+    let next_literal = &rest[..rest.find('{').unwrap_or(rest.len())];
+    let end = if next_literal.is_empty() {
+      uri.len()
+    } else {
+      uri.find(next_literal)?
+    };
+    let value = &uri[..end];
+    if value.is_empty() || value.contains('/') {
+      return None;
+    }
+    let value = percent_decode_str(value).decode_utf8().ok()?;
+    arguments.insert(name.to_string(), value.into_owned());
+    uri = &uri[end..];
+    template = rest;
+  }
+  uri.is_empty().then_some(arguments)
+}
+
+// Broadcasts the URIs of resources whose contents changed, for
+// resources/subscribe. Clones publish to the same receivers.
+#[derive(Clone)]
+pub struct ResourceUpdates {
+  sender: broadcast::Sender<String>,
+}
+
+impl Default for ResourceUpdates {
+  fn default() -> Self {
+    Self {
+      sender: broadcast::channel(64).0,
+    }
+  }
+}
+
+impl ResourceUpdates {
+  pub fn new() -> Self {
+    Self::default()
+  }
+
+  // Dropped when nobody is listening
+  pub fn publish(&self, uri: impl Into<String>) {
+    let _ = self.sender.send(uri.into());
+  }
+
+  pub fn subscribe(&self) -> broadcast::Receiver<String> {
+    self.sender.subscribe()
+  }
+}
+
+// Lists a registry can change at runtime, for notifications/*/list_changed
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ListKind {
+  Tools,
+  Prompts,
+  Resources,
+}
+
+#[derive(Default)]
+struct Items {
+  tools: Vec<Arc<dyn Tool>>,
+  prompts: Vec<Arc<dyn Prompt>>,
+  resources: Vec<Arc<dyn Resource>>,
+  resource_templates: Vec<Arc<dyn ResourceTemplate>>,
+}
+
+// Tools, prompts and resources offered over MCP. Both SimpleMcpServer and
+// the in-process DeepSeekMcpTools are driven from a registry. Clones share
+// the same items, so whatever is registered or unregistered at runtime is
+// seen by every server session and announced through list_changes.
+#[derive(Clone)]
+pub struct McpRegistry {
+  items: Arc<RwLock<Items>>,
+  updates: ResourceUpdates,
+  list_changes: broadcast::Sender<ListKind>,
+}
+
+impl Default for McpRegistry {
+  fn default() -> Self {
+    Self {
+      items: Arc::default(),
+      updates: ResourceUpdates::default(),
+      list_changes: broadcast::channel(16).0,
+    }
+  }
+}
+
+impl McpRegistry {
+  pub fn new() -> Self {
+    Self::default()
+  }
+
+  pub fn with_tool(self, tool: impl Tool + 'static) -> Self {
+    self.register_tool(tool);
+    self
+  }
+
+  pub fn with_prompt(self, prompt: impl Prompt + 'static) -> Self {
+    self.register_prompt(prompt);
+    self
+  }
+
+  pub fn with_resource(self, resource: impl Resource + 'static) -> Self {
+    self.register_resource(resource);
+    self
+  }
+
+  pub fn with_resource_template(self, template: impl ResourceTemplate + 'static) -> Self {
+    self.register_resource_template(template);
+    self
+  }
+
+  // Where the registry's resources announce changes
+  pub fn with_resource_updates(mut self, updates: ResourceUpdates) -> Self {
+    self.updates = updates;
+    self
+  }
+
+  // Add a tool, replacing any tool with the same name
+  pub fn register_tool(&self, tool: impl Tool + 'static) {
+    let mut items = self.items.write().unwrap();
+    items
+      .tools
+      .retain(|existing| existing.name() != tool.name());
+    items.tools.push(Arc::new(tool));
+    drop(items);
+    self.list_changed(ListKind::Tools);
+  }
+
+  // Returns whether a tool was removed
+  pub fn unregister_tool(&self, name: &str) -> bool {
+    let mut items = self.items.write().unwrap();
+    let count = items.tools.len();
+    items.tools.retain(|tool| tool.name() != name);
+    let removed = items.tools.len() != count;
+    drop(items);
+    if removed {
+      self.list_changed(ListKind::Tools);
+    }
+    removed
+  }
+
+  // Add a prompt, replacing any prompt with the same name
+  pub fn register_prompt(&self, prompt: impl Prompt + 'static) {
+    let mut items = self.items.write().unwrap();
+    items
+      .prompts
+      .retain(|existing| existing.name() != prompt.name());
+    items.prompts.push(Arc::new(prompt));
+    drop(items);
+    self.list_changed(ListKind::Prompts);
+  }
+
+  pub fn unregister_prompt(&self, name: &str) -> bool {
+    let mut items = self.items.write().unwrap();
+    let count = items.prompts.len();
+    items.prompts.retain(|prompt| prompt.name() != name);
+    let removed = items.prompts.len() != count;
+    drop(items);
+    if removed {
+      self.list_changed(ListKind::Prompts);
+    }
+    removed
+  }
+
+  // Add a resource, replacing any resource with the same URI
+  pub fn register_resource(&self, resource: impl Resource + 'static) {
+    let mut items = self.items.write().unwrap();
+    items
+      .resources
+      .retain(|existing| existing.uri() != resource.uri());
+    items.resources.push(Arc::new(resource));
+    drop(items);
+    self.list_changed(ListKind::Resources);
+  }
+
+  pub fn unregister_resource(&self, uri: &str) -> bool {
+    let mut items = self.items.write().unwrap();
+    let count = items.resources.len();
+    items.resources.retain(|resource| resource.uri() != uri);
+    let removed = items.resources.len() != count;
+    drop(items);
+    if removed {
+      self.list_changed(ListKind::Resources);
+    }
+    removed
+  }
+
+  // Add a resource template, replacing any template with the same URI template
+  pub fn register_resource_template(&self, template: impl ResourceTemplate + 'static) {
+    let mut items = self.items.write().unwrap();
+    items
+      .resource_templates
+      .retain(|existing| existing.uri_template() != template.uri_template());
+    items.resource_templates.push(Arc::new(template));
+    drop(items);
+    self.list_changed(ListKind::Resources);
+  }
+
+  pub fn unregister_resource_template(&self, uri_template: &str) -> bool {
+    let mut items = self.items.write().unwrap();
+    let count = items.resource_templates.len();
+    items
+      .resource_templates
+      .retain(|template| template.uri_template() != uri_template);
+    let removed = items.resource_templates.len() != count;
+    drop(items);
+    if removed {
+      self.list_changed(ListKind::Resources);
+    }
+    removed
+  }
+
+  fn list_changed(&self, kind: ListKind) {
+    // Nobody listening while the registry is being built
+    let _ = self.list_changes.send(kind);
+  }
+
+  // Every later register/unregister, by the kind of list it changed
+  pub fn list_changes(&self) -> broadcast::Receiver<ListKind> {
+    self.list_changes.subscribe()
+  }
+
+  pub fn tools(&self) -> Vec<Arc<dyn Tool>> {
+    self.items.read().unwrap().tools.clone()
+  }
+
+  pub fn prompts(&self) -> Vec<Arc<dyn Prompt>> {
+    self.items.read().unwrap().prompts.clone()
+  }
+
+  pub fn resources(&self) -> Vec<Arc<dyn Resource>> {
+    self.items.read().unwrap().resources.clone()
+  }
+
+  pub fn resource_templates(&self) -> Vec<Arc<dyn ResourceTemplate>> {
+    self.items.read().unwrap().resource_templates.clone()
+  }
+
+  pub fn resource_updates(&self) -> &ResourceUpdates {
+    &self.updates
+  }
+
+  pub fn tool(&self, name: &str) -> Option<Arc<dyn Tool>> {
+    let items = self.items.read().unwrap();
+    items.tools.iter().find(|tool| tool.name() == name).cloned()
+  }
+
+  pub fn prompt(&self, name: &str) -> Option<Arc<dyn Prompt>> {
+    let items = self.items.read().unwrap();
+    items
+      .prompts
+      .iter()
+      .find(|prompt| prompt.name() == name)
+      .cloned()
+  }
+
+  pub fn resource(&self, uri: &str) -> Option<Arc<dyn Resource>> {
+    let items = self.items.read().unwrap();
+    items
+      .resources
+      .iter()
+      .find(|resource| resource.uri() == uri)
+      .cloned()
+  }
+
+  // Whether `uri` names a resource or matches a template
+  pub fn has_resource(&self, uri: &str) -> bool {
+    self.resource(uri).is_some() || self.resource_template(uri).is_some()
+  }
+
+  // First template matching `uri`, with the variables taken from it
+  pub fn resource_template(
+    &self,
+    uri: &str,
+  ) -> Option<(Arc<dyn ResourceTemplate>, TemplateArguments)> {
+    let items = self.items.read().unwrap();
+    items.resource_templates.iter().find_map(|template| {
+      match_uri_template(template.uri_template(), uri)
+        .map(|arguments| (template.clone(), arguments))
+    })
+  }
+}
@@ -0,0 +1,594 @@
+use serde_json::{Map, Value};
+use std::fmt;
+
+// Validation of tool arguments against a tool's `inputSchema`. Covers the
+// JSON Schema keywords tool schemas use in practice: type, enum, const,
+// properties, required, additionalProperties, items, the length and range
+// limits, and allOf/anyOf/oneOf. Other keywords (pattern, format, $ref, ...)
+// are ignored rather than rejected.
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct SchemaViolation {
+  // Location of the offending value, `$` is the arguments object itself
+  pub path: String,
+  pub message: String,
+}
+
+impl fmt::Display for SchemaViolation {
+  fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+    write!(f, "{}: {}", self.path, self.message)
+  }
+}
+
+// Arguments that do not match the schema, with every violation found
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct InvalidArguments {
+  pub tool: String,
+  pub violations: Vec<SchemaViolation>,
+}
+
+impl InvalidArguments {
+  // `data` member of the -32602 JSON-RPC error
+  pub fn to_error_data(&self) -> Value {
+    Value::Array(
+      self
+        .violations
+        .iter()
+        .map(|violation| {
+          serde_json::json!({
+            "path": violation.path,
+            "message": violation.message
+          })
+        })
+        .collect(),
+    )
+  }
+}
+
+impl fmt::Display for InvalidArguments {
+  fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+    write!(f, "Invalid arguments for tool {}:", self.tool)?;
+    for violation in &self.violations {
+      write!(f, "\n- {}", violation)?;
+    }
+    Ok(())
+  }
+}
+
+impl std::error::Error for InvalidArguments {}
+
+// Check the arguments of a call to `tool` against its input schema
+pub fn validate_arguments(
+  tool: &str,
+  schema: &Value,
+  arguments: &Value,
+) -> Result<(), InvalidArguments> {
+  let violations = validate(schema, arguments);
+  if violations.is_empty() {
+    Ok(())
+  } else {
+    Err(InvalidArguments {
+      tool: tool.to_string(),
+      violations,
+    })
+  }
+}
+
+// All violations of `schema` by `instance`, empty when it is valid
+pub fn validate(schema: &Value, instance: &Value) -> Vec<SchemaViolation> {
+  let mut violations = Vec::new();
+  validate_at(schema, instance, "$", &mut violations);
+  violations
+}
+
+fn validate_at(
+  schema: &Value,
+  instance: &Value,
+  path: &str,
+  violations: &mut Vec<SchemaViolation>,
+) {
+  let schema = match schema {
+    Value::Object(schema) => schema,
+    // `false` accepts nothing, `true` and anything else accept everything
+    Value::Bool(false) => {
+      push(violations, path, "no value is allowed here".to_string());
+      return;
+    }
+    _ => return,
+  };
+
+  if let Some(expected) = schema.get("type")
+    && !matches_type(expected, instance)
+  {
+    push(
+      violations,
+      path,
+      format!(
+        "expected {}, got {}",
+        describe_type(expected),
+        type_name(instance)
+      ),
+    );
+    // The remaining keywords would only repeat the type mismatch
+    return;
+  }
+
+  if let Some(Value::Array(allowed)) = schema.get("enum")
+    && !allowed.contains(instance)
+  {
+    push(
+      violations,
+      path,
+      format!("must be one of {}", Value::Array(allowed.clone())),
+    );
+  }
+
+  if let Some(constant) = schema.get("const")
+    && constant != instance
+  {
+    push(violations, path, format!("must be {}", constant));
+  }
+
+  match instance {
+    Value::Object(object) => validate_object(schema, object, path, violations),
+    Value::Array(items) => validate_array(schema, items, path, violations),
+    Value::String(text) => validate_string(schema, text, path, violations),
+    Value::Number(_) => validate_number(schema, instance, path, violations),
+    _ => {}
+  }
+
+  validate_combinators(schema, instance, path, violations);
+}
+
+fn validate_object(
+  schema: &Map<String, Value>,
+  object: &Map<String, Value>,
+  path: &str,
+  violations: &mut Vec<SchemaViolation>,
+) {
+  if let Some(Value::Array(required)) = schema.get("required") {
+    for name in required.iter().filter_map(Value::as_str) {
+      if !object.contains_key(name) {
+        push(
+          violations,
+          &child_path(path, name),
+          "is required".to_string(),
+        );
+      }
+    }
+  }
+
+  let properties = schema.get("properties").and_then(Value::as_object);
+  for (name, value) in object {
+    let property_path = child_path(path, name);
+    match properties.and_then(|properties| properties.get(name)) {
+      Some(property) => validate_at(property, value, &property_path, violations),
+      None => match schema.get("additionalProperties") {
+        Some(Value::Bool(false)) => push(
+          violations,
+          &property_path,
+          "is not an allowed property".to_string(),
+        ),
+        Some(additional) => validate_at(additional, value, &property_path, violations),
+        None => {}
+      },
+    }
+  }
+}
+
+fn validate_array(
+  schema: &Map<String, Value>,
+  items: &[Value],
+  path: &str,
+  violations: &mut Vec<SchemaViolation>,
+) {
+  if let Some(min) = schema.get("minItems").and_then(Value::as_u64)
+    && (items.len() as u64) < min
+  {
+    push(
+      violations,
+      path,
+      format!("must have at least {} item(s)", min),
+    );
+  }
+  if let Some(max) = schema.get("maxItems").and_then(Value::as_u64)
+    && (items.len() as u64) > max
+  {
+    push(
+      violations,
+      path,
+      format!("must have at most {} item(s)", max),
+    );
+  }
+
+  if let Some(item_schema) = schema.get("items") {
+    for (index, item) in items.iter().enumerate() {
+      validate_at(
+        item_schema,
+        item,
+        &format!("{}[{}]", path, index),
+        violations,
+      );
+    }
+  }
+}
+
+fn validate_string(
+  schema: &Map<String, Value>,
+  text: &str,
+  path: &str,
+  violations: &mut Vec<SchemaViolation>,
+) {
+  let length = text.chars().count() as u64;
+  if let Some(min) = schema.get("minLength").and_then(Value::as_u64)
+    && length < min
+  {
+    push(
+      violations,
+      path,
+      format!("must be at least {} character(s) long", min),
+    );
+  }
+  if let Some(max) = schema.get("maxLength").and_then(Value::as_u64)
+    && length > max
+  {
+    push(
+      violations,
+      path,
+      format!("must be at most {} character(s) long", max),
+    );
+  }
+}
+
+fn validate_number(
+  schema: &Map<String, Value>,
+  instance: &Value,
+  path: &str,
+  violations: &mut Vec<SchemaViolation>,
+) {
+  let Some(number) = instance.as_f64() else {
+    return;
+  };
+  let limit = |keyword: &str| schema.get(keyword).and_then(Value::as_f64);
+
+  if let Some(minimum) = limit("minimum")
+    && number < minimum
+  {
+    push(violations, path, format!("must be >= {}", minimum));
+  }
+  if let Some(maximum) = limit("maximum")
+    && number > maximum
+  {
+    push(violations, path, format!("must be <= {}", maximum));
+  }
+  if let Some(minimum) = limit("exclusiveMinimum")
+    && number <= minimum
+  {
+    push(violations, path, format!("must be > {}", minimum));
+  }
+  if let Some(maximum) = limit("exclusiveMaximum")
+    && number >= maximum
+  {
+    push(violations, path, format!("must be < {}", maximum));
+  }
+}
+
+fn validate_combinators(
+  schema: &Map<String, Value>,
+  instance: &Value,
+  path: &str,
+  violations: &mut Vec<SchemaViolation>,
+) {
+  if let Some(Value::Array(schemas)) = schema.get("allOf") {
+    for schema in schemas {
+      validate_at(schema, instance, path, violations);
+    }
+  }
+
+  let matching = |schemas: &[Value]| {
+    schemas
+      .iter()
+      .filter(|schema| validate(schema, instance).is_empty())
+      .count()
+  };
+  if let Some(Value::Array(schemas)) = schema.get("anyOf")
+    && matching(schemas) == 0
+  {
+    push(
+      violations,
+      path,
+      "does not match any of the allowed schemas".to_string(),
+    );
+  }
+  if let Some(Value::Array(schemas)) = schema.get("oneOf") {
+    let count = matching(schemas);
+    if count != 1 {
+      push(
+        violations,
+        path,
+        format!(
+          "must match exactly one of the allowed schemas, matches {}",
+          count
+        ),
+      );
+    }
+  }
+}
+
+fn matches_type(expected: &Value, instance: &Value) -> bool {
+  match expected {
+    Value::String(name) => is_type(name, instance),
+    Value::Array(names) => names
+      .iter()
+      .filter_map(Value::as_str)
+      .any(|name| is_type(name, instance)),
+    _ => true,
+  }
+}
+
+fn is_type(name: &str, instance: &Value) -> bool {
+  match name {
+    "object" => instance.is_object(),
+    "array" => instance.is_array(),
+    "string" => instance.is_string(),
+    "boolean" => instance.is_boolean(),
+    "null" => instance.is_null(),
+    "number" => instance.is_number(),
+    "integer" => instance.as_f64().is_some_and(|n| n.fract() == 0.0),
+    _ => true,
+  }
+}
+
+fn describe_type(expected: &Value) -> String {
+  match expected {
+    Value::Array(names) => names
+      .iter()
+      .filter_map(Value::as_str)
+      .collect::<Vec<_>>()
+      .join(" or "),
+    other => other.as_str().unwrap_or("any").to_string(),
+  }
+}
+
+fn type_name(instance: &Value) -> &'static str {
+  match instance {
+    Value::Null => "null",
+    Value::Bool(_) => "boolean",
+    Value::Number(_) => "number",
+    Value::String(_) => "string",
+    Value::Array(_) => "array",
+    Value::Object(_) => "object",
+  }
+}
+
+fn child_path(path: &str, name: &str) -> String {
+  format!("{}.{}", path, name)
+}
+
+fn push(violations: &mut Vec<SchemaViolation>, path: &str, message: String) {
+  violations.push(SchemaViolation {
+    path: path.to_string(),
+    message,
+  });
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+  use crate::mcp_protocol::{INVALID_PARAMS, JsonRpcError};
+  use serde_json::json;
+
+  // (path, message) of every violation, in the order they were found
+  fn violations(schema: Value, instance: Value) -> Vec<(String, String)> {
+    validate(&schema, &instance)
+      .into_iter()
+      .map(|violation| (violation.path, violation.message))
+      .collect()
+  }
+
+  fn violation(path: &str, message: &str) -> (String, String) {
+    (path.to_string(), message.to_string())
+  }
+
+  #[test]
+  fn type_mismatches_stop_at_the_type() {
+    let schema = json!({"type": "string", "minLength": 3});
+    assert!(violations(schema.clone(), json!("abc")).is_empty());
+    assert_eq!(
+      violations(schema, json!(1)),
+      [violation("$", "expected string, got number")]
+    );
+
+    let schema = json!({"type": ["integer", "null"]});
+    assert!(violations(schema.clone(), json!(2)).is_empty());
+    assert!(violations(schema.clone(), json!(2.0)).is_empty());
+    assert!(violations(schema.clone(), Value::Null).is_empty());
+    assert_eq!(
+      violations(schema, json!(2.5)),
+      [violation("$", "expected integer or null, got number")]
+    );
+  }
+
+  #[test]
+  fn enum_and_const_compare_whole_values() {
+    let schema = json!({"enum": ["metric", "imperial"]});
+    assert!(violations(schema.clone(), json!("metric")).is_empty());
+    assert_eq!(
+      violations(schema, json!("kelvin")),
+      [violation("$", r#"must be one of ["metric","imperial"]"#)]
+    );
+
+    let schema = json!({"const": {"a": 1}});
+    assert!(violations(schema.clone(), json!({"a": 1})).is_empty());
+    assert_eq!(
+      violations(schema, json!({"a": 2})),
+      [violation("$", r#"must be {"a":1}"#)]
+    );
+  }
+
+  #[test]
+  fn required_and_additional_properties() {
+    let schema = json!({
+      "type": "object",
+      "properties": {
+        "city": {"type": "string"},
+        "days": {"type": "integer"}
+      },
+      "required": ["city", "days"],
+      "additionalProperties": false
+    });
+    assert!(violations(schema.clone(), json!({"city": "北京", "days": 3})).is_empty());
+    assert_eq!(
+      violations(schema, json!({"days": "3", "extra": true})),
+      [
+        violation("$.city", "is required"),
+        violation("$.days", "expected integer, got string"),
+        violation("$.extra", "is not an allowed property"),
+      ]
+    );
+
+    // A schema for additional properties checks every unlisted one
+    let schema = json!({"type": "object", "additionalProperties": {"type": "number"}});
+    assert_eq!(
+      violations(schema, json!({"a": 1, "b": "2"})),
+      [violation("$.b", "expected number, got string")]
+    );
+  }
+
+  #[test]
+  fn items_are_checked_by_index() {
+    let schema = json!({
+      "type": "array",
+      "items": {
+        "type": "object",
+        "properties": {"tag": {"type": "string"}}
+      },
+      "minItems": 1,
+      "maxItems": 2
+    });
+    assert!(violations(schema.clone(), json!([{"tag": "a"}])).is_empty());
+    assert_eq!(
+      violations(schema.clone(), json!([])),
+      [violation("$", "must have at least 1 item(s)")]
+    );
+    assert_eq!(
+      violations(schema, json!([{"tag": "a"}, {"tag": 1}, 3])),
+      [
+        violation("$", "must have at most 2 item(s)"),
+        violation("$[1].tag", "expected string, got number"),
+        violation("$[2]", "expected object, got number"),
+      ]
+    );
+  }
+
+  #[test]
+  fn string_lengths_count_characters() {
+    let schema = json!({"type": "string", "minLength": 2, "maxLength": 3});
+    assert!(violations(schema.clone(), json!("北京市")).is_empty());
+    assert_eq!(
+      violations(schema.clone(), json!("北")),
+      [violation("$", "must be at least 2 character(s) long")]
+    );
+    assert_eq!(
+      violations(schema, json!("北京市区")),
+      [violation("$", "must be at most 3 character(s) long")]
+    );
+  }
+
+  #[test]
+  fn number_ranges() {
+    let schema = json!({"type": "number", "minimum": 1, "maximum": 10});
+    assert!(violations(schema.clone(), json!(1)).is_empty());
+    assert!(violations(schema.clone(), json!(10)).is_empty());
+    assert_eq!(
+      violations(schema.clone(), json!(0.5)),
+      [violation("$", "must be >= 1")]
+    );
+    assert_eq!(
+      violations(schema, json!(11)),
+      [violation("$", "must be <= 10")]
+    );
+
+    let schema = json!({"exclusiveMinimum": 0, "exclusiveMaximum": 1});
+    assert!(violations(schema.clone(), json!(0.5)).is_empty());
+    assert_eq!(
+      violations(schema.clone(), json!(0)),
+      [violation("$", "must be > 0")]
+    );
+    assert_eq!(
+      violations(schema, json!(1)),
+      [violation("$", "must be < 1")]
+    );
+  }
+
+  #[test]
+  fn combinators() {
+    let schema = json!({"allOf": [{"type": "integer"}, {"minimum": 5}]});
+    assert!(violations(schema.clone(), json!(5)).is_empty());
+    assert_eq!(
+      violations(schema, json!(4)),
+      [violation("$", "must be >= 5")]
+    );
+
+    let schema = json!({"anyOf": [{"type": "string"}, {"type": "integer"}]});
+    assert!(violations(schema.clone(), json!(1)).is_empty());
+    assert_eq!(
+      violations(schema, json!(true)),
+      [violation("$", "does not match any of the allowed schemas")]
+    );
+
+    let schema = json!({"oneOf": [{"type": "integer"}, {"minimum": 0}]});
+    assert!(violations(schema.clone(), json!(-1)).is_empty());
+    assert_eq!(
+      violations(schema.clone(), json!(1)),
+      [violation(
+        "$",
+        "must match exactly one of the allowed schemas, matches 2"
+      )]
+    );
+    assert_eq!(
+      violations(schema, json!(-0.5)),
+      [violation(
+        "$",
+        "must match exactly one of the allowed schemas, matches 0"
+      )]
+    );
+  }
+
+  #[test]
+  fn boolean_schemas() {
+    assert!(violations(json!(true), json!({"any": "thing"})).is_empty());
+    let schema = json!({"type": "object", "properties": {"never": false}});
+    assert!(violations(schema.clone(), json!({})).is_empty());
+    assert_eq!(
+      violations(schema, json!({"never": 1})),
+      [violation("$.never", "no value is allowed here")]
+    );
+  }
+
+  #[test]
+  fn invalid_arguments_become_invalid_params_with_violations() {
+    let schema = json!({
+      "type": "object",
+      "properties": {"location": {"type": "string"}},
+      "required": ["location"]
+    });
+    assert!(validate_arguments("get_weather", &schema, &json!({"location": "上海"})).is_ok());
+
+    let invalid = validate_arguments("get_weather", &schema, &json!({"location": 1})).unwrap_err();
+    assert_eq!(invalid.tool, "get_weather");
+    assert_eq!(
+      invalid.to_string(),
+      "Invalid arguments for tool get_weather:\n- $.location: expected string, got number"
+    );
+
+    let error = JsonRpcError::from(invalid);
+    assert_eq!(error.code, INVALID_PARAMS);
+    assert_eq!(
+      error.data,
+      Some(json!({
+        "violations": [{"path": "$.location", "message": "expected string, got number"}]
+      }))
+    );
+  }
+}
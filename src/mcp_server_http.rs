@@ -0,0 +1,106 @@
+use axum::extract::Request;
+use axum::http::{StatusCode, header};
+use axum::middleware::{self, Next};
+use axum::response::{IntoResponse, Response};
+use axum::{Json, Router};
+use rmcp::transport::streamable_http_server::session::local::LocalSessionManager;
+use rmcp::transport::streamable_http_server::{StreamableHttpServerConfig, StreamableHttpService};
+use serde_json::Value;
+use std::net::TcpListener;
+use tracing::info;
+
+use crate::mcp_protocol::JsonRpcError;
+use crate::mcp_server_simple::SimpleMcpServer;
+
+// Streamable HTTP transport, served by rmcp at a single endpoint where
+// - POST takes a JSON-RPC message; requests are answered with an event
+//   stream carrying any progress notifications and then the response.
+// - GET opens an event stream for server-initiated notifications.
+// - DELETE ends the session.
+// initialize starts a session whose id is returned in the Mcp-Session-Id
+// header; every later request must send it back. Requests without it get
+// 400 Bad Request, requests for an unknown or expired session 404 Not Found.
+
+pub const MCP_ENDPOINT: &str = "/mcp";
+
+pub const SESSION_ID_HEADER: &str = "mcp-session-id";
+
+// Serve `server` on an already bound listener until Ctrl-C
+pub async fn serve(
+  server: SimpleMcpServer,
+  listener: TcpListener,
+) -> Result<(), Box<dyn std::error::Error>> {
+  listener.set_nonblocking(true)?;
+  let listener = tokio::net::TcpListener::from_std(listener)?;
+
+  // Every session gets its own handler sharing the registry and request limit
+  let service = StreamableHttpService::new(
+    move || Ok(server.for_session()),
+    LocalSessionManager::default().into(),
+    StreamableHttpServerConfig::default(),
+  );
+  let app = Router::new()
+    .nest_service(MCP_ENDPOINT, service)
+    .layer(middleware::from_fn(session_errors))
+    .layer(middleware::from_fn(check_origin));
+
+  info!(
+    "MCP Server: Listening on http://{}{}",
+    listener.local_addr()?,
+    MCP_ENDPOINT
+  );
+  axum::serve(listener, app)
+    .with_graceful_shutdown(async {
+      let _ = tokio::signal::ctrl_c().await;
+    })
+    .await?;
+  Ok(())
+}
+
+// Guard against DNS rebinding: browsers may only call from a local page
+async fn check_origin(request: Request, next: Next) -> Response {
+  let origin = request
+    .headers()
+    .get(header::ORIGIN)
+    .and_then(|origin| origin.to_str().ok());
+  match origin {
+    Some(origin) if !is_local_origin(origin) => {
+      (StatusCode::FORBIDDEN, "Origin not allowed").into_response()
+    }
+    _ => next.run(request).await,
+  }
+}
+
+// rmcp answers a request without a session with 422 (POST) or 401 (GET,
+// DELETE) and one for an unknown session with 401, though no authorization
+// is involved; map them to the statuses the spec asks for
+async fn session_errors(request: Request, next: Next) -> Response {
+  let has_session = request.headers().contains_key(SESSION_ID_HEADER);
+  let response = next.run(request).await;
+
+  let (status, error) = match response.status() {
+    StatusCode::UNAUTHORIZED if has_session => (
+      StatusCode::NOT_FOUND,
+      JsonRpcError::invalid_request("Unknown or expired session"),
+    ),
+    StatusCode::UNAUTHORIZED | StatusCode::UNPROCESSABLE_ENTITY if !has_session => (
+      StatusCode::BAD_REQUEST,
+      JsonRpcError::invalid_request("Missing Mcp-Session-Id header"),
+    ),
+    _ => return response,
+  };
+  (status, Json(error.to_response(Value::Null))).into_response()
+}
+
+fn is_local_origin(origin: &str) -> bool {
+  let host = origin
+    .split("://")
+    .nth(1)
+    .unwrap_or(origin)
+    .trim_end_matches('/');
+  let host = match host.rsplit_once(':') {
+    Some((host, port)) if port.chars().all(|c| c.is_ascii_digit()) => host,
+    _ => host,
+  };
+  matches!(host, "localhost" | "127.0.0.1" | "[::1]")
+}
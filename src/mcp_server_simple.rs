@@ -1,340 +1,620 @@
-use serde_json::{json, Value};
+use percent_encoding::percent_decode_str;
+use rmcp::model::{
+  CallToolRequestParams, CallToolResult, CustomNotification, CustomRequest, CustomResult,
+  ErrorCode, ErrorData, GetPromptRequestParams, GetPromptResult, Implementation,
+  InitializeRequestParams, InitializeResult, ListPromptsResult, ListResourceTemplatesResult,
+  ListResourcesResult, ListToolsResult, PaginatedRequestParams, ProgressNotificationParam,
+  ProtocolVersion, ReadResourceRequestParams, ReadResourceResult, ResourceUpdatedNotificationParam,
+  ServerCapabilities, ServerInfo, ServerNotification, SubscribeRequestParams,
+  UnsubscribeRequestParams,
+};
+use rmcp::service::{NotificationContext, Peer, RequestContext, RoleServer, ServerInitializeError};
+use rmcp::{ServerHandler, ServiceExt};
+use serde::de::DeserializeOwned;
+use serde_json::{Value, json};
+use std::collections::HashMap;
 use std::env;
-use tokio::io::{AsyncBufReadExt, AsyncWriteExt, BufReader as AsyncBufReader};
-use tracing::{info, error};
+use std::net::{SocketAddr, TcpListener};
+use std::path::PathBuf;
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::{Arc, Mutex};
+use tokio::io::{AsyncRead, AsyncWrite};
+use tokio::sync::broadcast::error::RecvError;
+use tokio::sync::{OwnedSemaphorePermit, Semaphore, mpsc};
+use tokio_util::sync::{CancellationToken, DropGuard};
+use tracing::{error, info, warn};
 
-use crate::tools::amap::get_weather;
-use crate::tools::serper::{search_with_key, format_results};
+use crate::mcp_protocol::{JsonRpcError, REQUEST_CANCELLED, negotiate_version};
+use crate::mcp_registry::{ListKind, McpRegistry, ToolContext};
+use crate::mcp_schema::validate_arguments;
+use crate::mcp_server_http;
+use crate::mcp_server_stdio::StdioTransport;
+use crate::tools::default_registry;
 
-#[derive(Debug, Clone)]
+// Default limit on tool calls and resource reads running at the same time
+pub const DEFAULT_MAX_CONCURRENT_REQUESTS: usize = 8;
+
+// Default number of items in one page of a list reply
+pub const DEFAULT_PAGE_SIZE: usize = 50;
+
+// Methods this server implements. rmcp hands such a request to
+// on_custom_request when its params don't parse.
+const KNOWN_METHODS: &[&str] = &[
+  "initialize",
+  "ping",
+  "tools/list",
+  "tools/call",
+  "resources/list",
+  "resources/templates/list",
+  "resources/read",
+  "resources/subscribe",
+  "resources/unsubscribe",
+  "prompts/list",
+  "prompts/get",
+];
+
+static NEXT_SESSION_ID: AtomicU64 = AtomicU64::new(1);
+
+// Identifies a client session. Its token is cancelled once the last handler
+// of the session is dropped, i.e. when rmcp is done with the transport, and
+// stops the session's background tasks.
+struct Session {
+  id: u64,
+  token: CancellationToken,
+  _guard: DropGuard,
+}
+
+impl Session {
+  fn new() -> Arc<Self> {
+    let token = CancellationToken::new();
+    Arc::new(Self {
+      id: NEXT_SESSION_ID.fetch_add(1, Ordering::Relaxed),
+      _guard: token.clone().drop_guard(),
+      token,
+    })
+  }
+}
+
+// MCP server over the shared registry, built on rmcp's ServerHandler. rmcp
+// takes care of JSON-RPC framing, the initialize handshake, running requests
+// concurrently and cancellation; this type maps MCP requests onto the
+// registry. Clones share the request limit and the connected clients; each
+// session gets its own resource subscriptions and lifetime through
+// for_session.
+#[derive(Clone)]
 pub struct SimpleMcpServer {
-  amap_key: String,
-  serper_key: String,
+  registry: McpRegistry,
+  permits: Arc<Semaphore>,
+  // Every initialized client by session id, for server-initiated notifications
+  peers: Arc<Mutex<HashMap<u64, Peer<RoleServer>>>>,
+  session: Arc<Session>,
+  // URIs this session subscribed to, keyed by their percent-decoded form
+  subscriptions: Arc<Mutex<HashMap<String, String>>>,
+  page_size: usize,
 }
 
 impl SimpleMcpServer {
   pub fn new() -> Result<Self, Box<dyn std::error::Error>> {
     let amap_key = env::var("AMAP_API_KEY")?;
     let serper_key = env::var("SERPER_API_KEY")?;
-    
-    Ok(Self {
-      amap_key,
-      serper_key,
-    })
+    // Query history for the recent-queries resources survives restarts when set
+    let history_dir = env::var_os("MCP_HISTORY_DIR").map(PathBuf::from);
+
+    Ok(Self::with_registry(default_registry(
+      &amap_key,
+      &serper_key,
+      history_dir.as_deref(),
+    )))
   }
 
-  // MCP JSON-RPC message handling
-  pub async fn handle_message(&self, message: Value) -> Result<Value, Box<dyn std::error::Error>> {
-    let method = message["method"].as_str().unwrap_or("");
-    let id = message["id"].clone();
-    let params = message.get("params").cloned().unwrap_or(json!({}));
-
-    info!("MCP Server: Handling method: {}", method);
-
-    let result = match method {
-      "initialize" => self.handle_initialize(params).await?,
-      "tools/list" => self.handle_list_tools().await?,
-      "tools/call" => self.handle_call_tool(params).await?,
-      "resources/list" => self.handle_list_resources().await?,
-      "resources/read" => self.handle_read_resource(params).await?,
-      "prompts/list" => self.handle_list_prompts().await?,
-      "prompts/get" => self.handle_get_prompt(params).await?,
-      _ => {
-        return Ok(json!({
-          "jsonrpc": "2.0",
-          "id": id,
-          "error": {
-            "code": -32601,
-            "message": "Method not found"
-          }
-        }));
-      }
-    };
+  // Serve the tools, prompts and resources of `registry`
+  pub fn with_registry(registry: McpRegistry) -> Self {
+    Self {
+      registry,
+      permits: Arc::new(Semaphore::new(DEFAULT_MAX_CONCURRENT_REQUESTS)),
+      peers: Arc::default(),
+      session: Session::new(),
+      subscriptions: Arc::default(),
+      page_size: DEFAULT_PAGE_SIZE,
+    }
+  }
 
-    Ok(json!({
-      "jsonrpc": "2.0",
-      "id": id,
-      "result": result
-    }))
+  // Handler for a new client session, sharing everything but the
+  // resource subscriptions and the session lifetime
+  pub(crate) fn for_session(&self) -> Self {
+    Self {
+      session: Session::new(),
+      subscriptions: Arc::default(),
+      ..self.clone()
+    }
   }
 
-  async fn handle_initialize(&self, _params: Value) -> Result<Value, Box<dyn std::error::Error>> {
-    Ok(json!({
-      "protocolVersion": "2024-11-05",
-      "capabilities": {
-        "tools": {},
-        "resources": {},
-        "prompts": {}
-      },
-      "serverInfo": {
-        "name": "deepseek-agent",
-        "version": "0.4.0"
-      }
-    }))
+  // Registry being served. Tools, prompts and resources registered or
+  // unregistered on it are announced to the connected clients.
+  pub fn registry(&self) -> &McpRegistry {
+    &self.registry
   }
 
-  async fn handle_list_tools(&self) -> Result<Value, Box<dyn std::error::Error>> {
-    Ok(json!({
-      "tools": [
-        {
-          "name": "get_weather",
-          "description": "获取指定城市的天气预报信息",
-          "inputSchema": {
-            "type": "object",
-            "properties": {
-              "location": {
-                "type": "string",
-                "description": "城市名称，例如：上海"
-              }
-            },
-            "required": ["location"]
-          }
-        },
-        {
-          "name": "search",
-          "description": "使用Google搜索获取实时信息",
-          "inputSchema": {
-            "type": "object",
-            "properties": {
-              "query": {
-                "type": "string",
-                "description": "搜索查询词"
-              }
-            },
-            "required": ["query"]
-          }
-        }
-      ]
-    }))
+  // Number of items per tools/list, resources/list, resources/templates/list
+  // and prompts/list reply (at least 1)
+  pub fn with_page_size(mut self, page_size: usize) -> Self {
+    self.page_size = page_size.max(1);
+    self
   }
 
-  async fn handle_call_tool(&self, params: Value) -> Result<Value, Box<dyn std::error::Error>> {
-    let name = params["name"].as_str().ok_or("Missing tool name")?;
-    let arguments = params.get("arguments").cloned().unwrap_or(json!({}));
-
-    match name {
-      "get_weather" => {
-        let location = arguments["location"].as_str().ok_or("Missing location parameter")?;
-        
-        match get_weather(location, &self.amap_key).await {
-          Ok(weather_info) => {
-            let content = serde_json::to_string_pretty(&weather_info)?;
-            Ok(json!({
-              "content": [{
-                "type": "text",
-                "text": format!("天气信息获取成功：\n{}", content)
-              }],
-              "isError": false
-            }))
-          }
-          Err(e) => {
-            Ok(json!({
-              "content": [{
-                "type": "text", 
-                "text": format!("Weather API error: {}", e)
-              }],
-              "isError": true
-            }))
-          }
-        }
-      }
-      "search" => {
-        let query = arguments["query"].as_str().ok_or("Missing query parameter")?;
-        
-        match search_with_key(query, &self.serper_key).await {
-          Ok(search_results) => {
-            let formatted_results = format_results(&search_results, 3);
-            Ok(json!({
-              "content": [{
-                "type": "text",
-                "text": format!("搜索结果：\n{}", formatted_results)
-              }],
-              "isError": false
-            }))
-          }
-          Err(e) => {
-            Ok(json!({
-              "content": [{
-                "type": "text",
-                "text": format!("Search API error: {}", e)
-              }],
-              "isError": true
-            }))
-          }
+  // Limit on tool calls and resource reads running at the same time (at least 1)
+  pub fn with_max_concurrent_requests(mut self, limit: usize) -> Self {
+    self.permits = Arc::new(Semaphore::new(limit.max(1)));
+    self
+  }
+
+  // Number of initialized sessions still connected
+  pub fn session_count(&self) -> usize {
+    self.peers.lock().unwrap().len()
+  }
+
+  // Send a server-initiated notification to every connected client. Over
+  // HTTP it is dropped for clients without an open GET event stream.
+  pub fn notify(&self, method: &str, params: Value) {
+    let mut peers = self.peers.lock().unwrap();
+    peers.retain(|_, peer| !peer.is_transport_closed());
+    for peer in peers.values() {
+      let peer = peer.clone();
+      let notification = CustomNotification::new(method, Some(params.clone()));
+      tokio::spawn(async move {
+        let notification = ServerNotification::CustomNotification(notification);
+        if let Err(e) = peer.send_notification(notification).await {
+          warn!("MCP Server: Failed to send notification: {}", e);
         }
-      }
-      _ => {
-        Err(format!("Unknown tool: {}", name).into())
-      }
+      });
     }
   }
 
-  async fn handle_list_resources(&self) -> Result<Value, Box<dyn std::error::Error>> {
-    Ok(json!({
-      "resources": [
-        {
-          "uri": "weather://recent-queries",
-          "name": "Recent Weather Queries",
-          "description": "Recently queried weather locations",
-          "mimeType": "application/json"
-        },
-        {
-          "uri": "search://recent-queries",
-          "name": "Recent Search Queries", 
-          "description": "Recently performed search queries",
-          "mimeType": "application/json"
-        }
-      ]
-    }))
+  // Run the MCP server on stdio until the client closes stdin
+  pub async fn run_stdio(&self) -> Result<(), Box<dyn std::error::Error>> {
+    info!("MCP Server: Starting stdio server...");
+    self
+      .run_stdio_with(tokio::io::stdin(), tokio::io::stdout())
+      .await
   }
 
-  async fn handle_read_resource(&self, params: Value) -> Result<Value, Box<dyn std::error::Error>> {
-    let uri = params["uri"].as_str().ok_or("Missing resource URI")?;
-    
-    match uri {
-      "weather://recent-queries" => {
-        Ok(json!({
-          "contents": [{
-            "type": "text",
-            "text": r#"{"recent_queries": ["上海", "北京", "深圳"]}"#
-          }]
-        }))
+  // The stdio protocol over any pair of byte streams, such as a socket or an
+  // in-memory pipe
+  pub async fn run_stdio_with<R, W>(
+    &self,
+    reader: R,
+    writer: W,
+  ) -> Result<(), Box<dyn std::error::Error>>
+  where
+    R: AsyncRead + Unpin + Send + 'static,
+    W: AsyncWrite + Unpin + Send + 'static,
+  {
+    let transport = StdioTransport::new(reader, writer);
+    let service = match self.for_session().serve(transport).await {
+      Ok(service) => service,
+      // stdin closed before the handshake completed
+      Err(ServerInitializeError::ConnectionClosed(_)) => {
+        info!("MCP Server: Shutting down before initialization");
+        return Ok(());
       }
-      "search://recent-queries" => {
-        Ok(json!({
-          "contents": [{
-            "type": "text", 
-            "text": r#"{"recent_queries": ["2025年编程语言排行榜", "MCP协议", "Rust开发"]}"#
-          }]
-        }))
+      Err(e) => return Err(e.into()),
+    };
+    let reason = service.waiting().await?;
+    info!("MCP Server: Shutting down ({:?})", reason);
+    Ok(())
+  }
+
+  // Run the MCP server as a Streamable HTTP endpoint at `addr`
+  pub async fn run_http(&self, addr: SocketAddr) -> Result<(), Box<dyn std::error::Error>> {
+    let listener = TcpListener::bind(addr)?;
+    mcp_server_http::serve(self.clone(), listener).await
+  }
+
+  // Wait for a free permit; gives up when the request is cancelled meanwhile
+  async fn acquire_permit(
+    &self,
+    context: &RequestContext<RoleServer>,
+  ) -> Result<OwnedSemaphorePermit, ErrorData> {
+    tokio::select! {
+      permit = self.permits.clone().acquire_owned() => {
+        permit.map_err(|e| ErrorData::internal_error(e.to_string(), None))
       }
-      _ => Err(format!("Unknown resource URI: {}", uri).into())
+      _ = context.ct.cancelled() => Err(cancelled()),
     }
   }
 
-  async fn handle_list_prompts(&self) -> Result<Value, Box<dyn std::error::Error>> {
-    Ok(json!({
-      "prompts": [
-        {
-          "name": "weather_advisor",
-          "description": "专业的天气顾问提示模板",
-          "arguments": [{
-            "name": "weather_data",
-            "description": "天气数据JSON",
-            "required": true
-          }]
-        },
-        {
-          "name": "search_analyzer",
-          "description": "搜索结果分析师提示模板", 
-          "arguments": [{
-            "name": "search_results",
-            "description": "搜索结果数据",
-            "required": true
-          }]
-        }
-      ]
-    }))
+  // One page of a list reply under `key`. The cursor is the offset of the
+  // page's first item; nextCursor is only set when more items follow.
+  fn page(
+    &self,
+    key: &str,
+    items: Vec<Value>,
+    request: Option<PaginatedRequestParams>,
+  ) -> Result<Value, ErrorData> {
+    let start = match request.and_then(|request| request.cursor) {
+      Some(cursor) => cursor
+        .parse::<usize>()
+        .ok()
+        .filter(|start| *start <= items.len())
+        .ok_or_else(|| ErrorData::invalid_params(format!("Invalid cursor: {}", cursor), None))?,
+      None => 0,
+    };
+    let end = items.len().min(start + self.page_size);
+
+    let mut page = json!({ key: items[start..end] });
+    if end < items.len() {
+      page["nextCursor"] = json!(end.to_string());
+    }
+    Ok(page)
   }
 
-  async fn handle_get_prompt(&self, params: Value) -> Result<Value, Box<dyn std::error::Error>> {
-    let name = params["name"].as_str().ok_or("Missing prompt name")?;
-    let arguments = params.get("arguments").cloned().unwrap_or(json!({}));
-
-    match name {
-      "weather_advisor" => {
-        let weather_data = arguments["weather_data"].as_str().ok_or("Missing weather_data parameter")?;
-        
-        Ok(json!({
-          "description": "天气顾问专业分析",
-          "messages": [
-            {
-              "role": "system",
-              "content": {
-                "type": "text",
-                "text": "你是一个专业的天气顾问，请根据获取到的天气数据给出详细的穿衣建议。注意：\n1. 分析温度范围和温差\n2. 考虑天气现象（晴、阴、雨等）\n3. 考虑风力大小\n4. 给出具体的穿衣层次建议\n5. 如有必要，提醒是否需要携带雨具或防晒用品"
-              }
-            },
-            {
-              "role": "user", 
-              "content": {
-                "type": "text",
-                "text": format!("请分析以下天气数据并给出建议：\n{}", weather_data)
-              }
+  // Send `peer` notifications/resources/updated for every changed resource
+  // this session subscribed to, and notifications/*/list_changed whenever
+  // tools, prompts or resources are registered or unregistered. Stops and
+  // forgets the peer when the session ends.
+  fn forward_notifications(&self, peer: Peer<RoleServer>) {
+    let id = self.session.id;
+    let closed = self.session.token.clone();
+    let peers = self.peers.clone();
+    let subscriptions = self.subscriptions.clone();
+    let mut updates = self.registry.resource_updates().subscribe();
+    let mut changes = self.registry.list_changes();
+    peers.lock().unwrap().insert(id, peer.clone());
+
+    tokio::spawn(async move {
+      loop {
+        tokio::select! {
+          _ = closed.cancelled() => break,
+          update = updates.recv() => match update {
+            Ok(uri) => send_resource_update(&peer, &subscriptions, &uri).await,
+            Err(RecvError::Lagged(skipped)) => {
+              warn!("MCP Server: Missed {} resource updates", skipped);
             }
-          ]
-        }))
-      }
-      "search_analyzer" => {
-        let search_results = arguments["search_results"].as_str().ok_or("Missing search_results parameter")?;
-        
-        Ok(json!({
-          "description": "搜索结果专业分析",
-          "messages": [
-            {
-              "role": "system",
-              "content": {
-                "type": "text", 
-                "text": "你是一个专业的信息分析师，请根据搜索结果给出准确、简洁的回答。"
-              }
-            },
-            {
-              "role": "user",
-              "content": {
-                "type": "text",
-                "text": format!("请分析以下搜索结果：\n{}", search_results)
+            Err(RecvError::Closed) => break,
+          },
+          change = changes.recv() => match change {
+            Ok(kind) => send_list_change(&peer, kind).await,
+            // Too far behind to tell what changed, so report everything
+            Err(RecvError::Lagged(_)) => {
+              for kind in [ListKind::Tools, ListKind::Prompts, ListKind::Resources] {
+                send_list_change(&peer, kind).await;
               }
             }
-          ]
-        }))
+            Err(RecvError::Closed) => break,
+          },
+        }
       }
-      _ => Err(format!("Unknown prompt: {}", name).into())
+      peers.lock().unwrap().remove(&id);
+      info!("MCP Server: Session {} closed", id);
+    });
+  }
+}
+
+impl ServerHandler for SimpleMcpServer {
+  fn get_info(&self) -> ServerInfo {
+    ServerInfo {
+      protocol_version: protocol_version(negotiate_version(None)),
+      capabilities: ServerCapabilities::builder()
+        .enable_tools()
+        .enable_tool_list_changed()
+        .enable_resources()
+        .enable_resources_subscribe()
+        .enable_resources_list_changed()
+        .enable_prompts()
+        .enable_prompts_list_changed()
+        .build(),
+      server_info: Implementation {
+        name: "deepseek-agent".to_string(),
+        version: env!("CARGO_PKG_VERSION").to_string(),
+        ..Implementation::from_build_env()
+      },
+      instructions: None,
     }
   }
 
-  // Run the MCP server on stdio
-  pub async fn run_stdio(&self) -> Result<(), Box<dyn std::error::Error>> {
-    info!("MCP Server: Starting stdio server...");
-    
-    let stdin = tokio::io::stdin();
-    let mut stdout = tokio::io::stdout();
-    let mut reader = AsyncBufReader::new(stdin);
-    let mut line = String::new();
-
-    loop {
-      line.clear();
-      let bytes_read = reader.read_line(&mut line).await?;
-      
-      if bytes_read == 0 {
-        info!("MCP Server: EOF received, shutting down");
-        break;
-      }
+  async fn initialize(
+    &self,
+    request: InitializeRequestParams,
+    context: RequestContext<RoleServer>,
+  ) -> Result<InitializeResult, ErrorData> {
+    let requested = request.protocol_version.to_string();
+    let version = negotiate_version(Some(&requested));
+    info!(
+      "MCP Server: Client requested protocol {}, using {}",
+      requested, version
+    );
+
+    if context.peer.peer_info().is_none() {
+      context.peer.set_peer_info(request);
+    }
+    Ok(ServerInfo {
+      protocol_version: protocol_version(version),
+      ..self.get_info()
+    })
+  }
+
+  async fn on_initialized(&self, context: NotificationContext<RoleServer>) {
+    let client = context
+      .peer
+      .peer_info()
+      .map(|info| info.client_info.name.clone())
+      .unwrap_or_default();
+    info!("MCP Server: Session ready ({})", client);
+    self.forward_notifications(context.peer);
+  }
+
+  async fn list_tools(
+    &self,
+    request: Option<PaginatedRequestParams>,
+    _context: RequestContext<RoleServer>,
+  ) -> Result<ListToolsResult, ErrorData> {
+    let tools: Vec<Value> = self
+      .registry
+      .tools()
+      .iter()
+      .map(|tool| tool.definition())
+      .collect();
+    typed(self.page("tools", tools, request)?)
+  }
+
+  async fn call_tool(
+    &self,
+    request: CallToolRequestParams,
+    context: RequestContext<RoleServer>,
+  ) -> Result<CallToolResult, ErrorData> {
+    let name = request.name.as_ref();
+    info!("MCP Server: Calling tool {}", name);
+    let arguments = Value::Object(request.arguments.clone().unwrap_or_default());
+
+    let tool = self
+      .registry
+      .tool(name)
+      .ok_or_else(|| ErrorData::invalid_params(format!("Unknown tool: {}", name), None))?;
+    // Arguments are checked against the tool's inputSchema before it runs
+    validate_arguments(name, &tool.input_schema(), &arguments)
+      .map_err(|e| ErrorData::from(JsonRpcError::from(e)))?;
+
+    let _permit = self.acquire_permit(&context).await?;
+    let (tool_context, progress) = tool_context(&context);
+    let output = tokio::select! {
+      output = tool.execute(arguments, tool_context) => output,
+      // Dropping the execution future stops the tool
+      _ = context.ct.cancelled() => return Err(cancelled()),
+    };
+    // Progress notifications must reach the client before the result
+    if let Some(progress) = progress {
+      let _ = progress.await;
+    }
+
+    let output = output.map_err(|e| {
+      error!("MCP Server: Tool {} failed: {}", name, e);
+      ErrorData::internal_error(e.to_string(), None)
+    })?;
+    typed(output.to_call_result())
+  }
+
+  async fn list_resources(
+    &self,
+    request: Option<PaginatedRequestParams>,
+    _context: RequestContext<RoleServer>,
+  ) -> Result<ListResourcesResult, ErrorData> {
+    let resources: Vec<Value> = self
+      .registry
+      .resources()
+      .iter()
+      .map(|resource| resource.definition())
+      .collect();
+    typed(self.page("resources", resources, request)?)
+  }
+
+  async fn list_resource_templates(
+    &self,
+    request: Option<PaginatedRequestParams>,
+    _context: RequestContext<RoleServer>,
+  ) -> Result<ListResourceTemplatesResult, ErrorData> {
+    let templates: Vec<Value> = self
+      .registry
+      .resource_templates()
+      .iter()
+      .map(|template| template.definition())
+      .collect();
+    typed(self.page("resourceTemplates", templates, request)?)
+  }
 
-      let line = line.trim();
-      if line.is_empty() {
-        continue;
+  async fn read_resource(
+    &self,
+    request: ReadResourceRequestParams,
+    context: RequestContext<RoleServer>,
+  ) -> Result<ReadResourceResult, ErrorData> {
+    let uri = request.uri.as_str();
+    // Fixed resources first, then the first template matching the URI
+    let resource = self.registry.resource(uri);
+    let template = self.registry.resource_template(uri);
+    let (mime_type, read) = match (&resource, &template) {
+      (Some(resource), _) => (resource.mime_type(), resource.read()),
+      (None, Some((template, arguments))) => {
+        (template.mime_type(), template.read(arguments.clone()))
+      }
+      (None, None) => {
+        return Err(ErrorData::invalid_params(
+          format!("Unknown resource URI: {}", uri),
+          None,
+        ));
       }
+    };
 
-      match serde_json::from_str::<Value>(line) {
-        Ok(message) => {
-          match self.handle_message(message).await {
-            Ok(response) => {
-              let response_str = serde_json::to_string(&response)?;
-              stdout.write_all(response_str.as_bytes()).await?;
-              stdout.write_all(b"\n").await?;
-              stdout.flush().await?;
-            }
-            Err(e) => {
-              error!("MCP Server: Error handling message: {}", e);
-            }
+    let _permit = self.acquire_permit(&context).await?;
+    let text = tokio::select! {
+      text = read => text,
+      _ = context.ct.cancelled() => return Err(cancelled()),
+    }
+    .map_err(|e| ErrorData::internal_error(e.to_string(), None))?;
+    typed(json!({
+      "contents": [{
+        "uri": uri,
+        "mimeType": mime_type,
+        "text": text
+      }]
+    }))
+  }
+
+  async fn subscribe(
+    &self,
+    request: SubscribeRequestParams,
+    _context: RequestContext<RoleServer>,
+  ) -> Result<(), ErrorData> {
+    if !self.registry.has_resource(&request.uri) {
+      return Err(ErrorData::invalid_params(
+        format!("Unknown resource URI: {}", request.uri),
+        None,
+      ));
+    }
+    info!("MCP Server: Subscribed to {}", request.uri);
+    let mut subscriptions = self.subscriptions.lock().unwrap();
+    subscriptions.insert(decode_uri(&request.uri), request.uri);
+    Ok(())
+  }
+
+  async fn unsubscribe(
+    &self,
+    request: UnsubscribeRequestParams,
+    _context: RequestContext<RoleServer>,
+  ) -> Result<(), ErrorData> {
+    info!("MCP Server: Unsubscribed from {}", request.uri);
+    let mut subscriptions = self.subscriptions.lock().unwrap();
+    subscriptions.remove(&decode_uri(&request.uri));
+    Ok(())
+  }
+
+  async fn list_prompts(
+    &self,
+    request: Option<PaginatedRequestParams>,
+    _context: RequestContext<RoleServer>,
+  ) -> Result<ListPromptsResult, ErrorData> {
+    let prompts: Vec<Value> = self
+      .registry
+      .prompts()
+      .iter()
+      .map(|prompt| prompt.definition())
+      .collect();
+    typed(self.page("prompts", prompts, request)?)
+  }
+
+  async fn get_prompt(
+    &self,
+    request: GetPromptRequestParams,
+    _context: RequestContext<RoleServer>,
+  ) -> Result<GetPromptResult, ErrorData> {
+    let name = request.name.as_str();
+    let arguments = Value::Object(request.arguments.unwrap_or_default());
+
+    let prompt = self
+      .registry
+      .prompt(name)
+      .ok_or_else(|| ErrorData::invalid_params(format!("Unknown prompt: {}", name), None))?;
+    // Prompts only fail on missing or malformed arguments
+    let result = prompt
+      .get(&arguments)
+      .map_err(|e| ErrorData::invalid_params(e.to_string(), None))?;
+    typed(result.to_get_result())
+  }
+
+  async fn on_custom_request(
+    &self,
+    request: CustomRequest,
+    _context: RequestContext<RoleServer>,
+  ) -> Result<CustomResult, ErrorData> {
+    let method = request.method.as_str();
+    if KNOWN_METHODS.contains(&method) {
+      return Err(ErrorData::invalid_params(
+        format!("Invalid params for {}", method),
+        None,
+      ));
+    }
+    Err(JsonRpcError::method_not_found(method).into())
+  }
+}
+
+// Progress is reported when the caller sent `_meta.progressToken`. The
+// notifications are forwarded in order by a task that finishes once the
+// returned ToolContext and all its clones are gone.
+fn tool_context(
+  context: &RequestContext<RoleServer>,
+) -> (ToolContext, Option<tokio::task::JoinHandle<()>>) {
+  let Some(token) = context.meta.get_progress_token() else {
+    return (ToolContext::new(), None);
+  };
+
+  let (sender, mut receiver) = mpsc::unbounded_channel::<Value>();
+  let peer = context.peer.clone();
+  let forward = tokio::spawn(async move {
+    while let Some(notification) = receiver.recv().await {
+      match serde_json::from_value::<ProgressNotificationParam>(notification["params"].clone()) {
+        Ok(params) => {
+          if let Err(e) = peer.notify_progress(params).await {
+            warn!("MCP Server: Failed to send progress: {}", e);
           }
         }
-        Err(e) => {
-          error!("MCP Server: Invalid JSON received: {}", e);
-        }
+        Err(e) => warn!("MCP Server: Invalid progress notification: {}", e),
       }
     }
+  });
 
-    Ok(())
+  let tool_context = ToolContext::with_progress(
+    json!(token.0),
+    Arc::new(move |notification| {
+      let _ = sender.send(notification);
+    }),
+  );
+  (tool_context, Some(forward))
+}
+
+// Notify `peer` of a change to `uri` when the session subscribed to it
+async fn send_resource_update(
+  peer: &Peer<RoleServer>,
+  subscriptions: &Mutex<HashMap<String, String>>,
+  uri: &str,
+) {
+  let subscribed = subscriptions.lock().unwrap().get(&decode_uri(uri)).cloned();
+  if let Some(uri) = subscribed
+    && let Err(e) = peer
+      .notify_resource_updated(ResourceUpdatedNotificationParam { uri })
+      .await
+  {
+    warn!("MCP Server: Failed to send resource update: {}", e);
   }
-}
\ No newline at end of file
+}
+
+async fn send_list_change(peer: &Peer<RoleServer>, kind: ListKind) {
+  info!("MCP Server: {:?} list changed", kind);
+  let sent = match kind {
+    ListKind::Tools => peer.notify_tool_list_changed().await,
+    ListKind::Prompts => peer.notify_prompt_list_changed().await,
+    ListKind::Resources => peer.notify_resource_list_changed().await,
+  };
+  if let Err(e) = sent {
+    warn!("MCP Server: Failed to send list change: {}", e);
+  }
+}
+
+// Subscribed and published URIs may differ in percent-encoding only
+fn decode_uri(uri: &str) -> String {
+  percent_decode_str(uri).decode_utf8_lossy().into_owned()
+}
+
+fn cancelled() -> ErrorData {
+  ErrorData::new(
+    ErrorCode(REQUEST_CANCELLED as i32),
+    "Request cancelled",
+    None,
+  )
+}
+
+// Registry items describe themselves as MCP JSON; rmcp wants its own types
+fn typed<T: DeserializeOwned>(value: Value) -> Result<T, ErrorData> {
+  serde_json::from_value(value).map_err(|e| ErrorData::internal_error(e.to_string(), None))
+}
+
+fn protocol_version(version: &str) -> ProtocolVersion {
+  serde_json::from_value(json!(version)).unwrap_or_default()
+}
@@ -0,0 +1,350 @@
+// Newline-delimited JSON-RPC transport for the MCP server on stdio.
+//
+// rmcp's own stdio transport stops at the first line it can't decode and
+// gives up on a session whose first message isn't initialize. This one keeps
+// the session alive instead:
+// - invalid JSON is answered with -32700 and messages that aren't JSON-RPC
+//   with -32600, both with a null id when the id is unknown
+// - a batch array hands its messages to rmcp one by one and its responses
+//   are written back as one array once all have arrived
+// - until the handshake has completed, requests other than initialize and
+//   ping are answered with -32600 and notifications are dropped
+// - the response to a request the client cancelled with
+//   notifications/cancelled is never written, as the MCP spec asks
+
+use rmcp::model::{ClientNotification, ClientRequest, JsonRpcMessage, RequestId};
+use rmcp::service::{RoleServer, RxJsonRpcMessage, TxJsonRpcMessage};
+use rmcp::transport::Transport;
+use serde_json::{Value, json};
+use std::collections::{HashSet, VecDeque};
+use std::io;
+use std::sync::{Arc, Mutex};
+use tokio::io::{AsyncBufReadExt, AsyncRead, AsyncWrite, AsyncWriteExt, BufReader, Lines};
+use tracing::{error, info, warn};
+
+use crate::mcp_protocol::{JsonRpcError, result_response};
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum SessionPhase {
+  Uninitialized,
+  // initialize handed to rmcp, waiting for notifications/initialized
+  Initializing,
+  Ready,
+}
+
+// What to do with one incoming message
+enum Incoming {
+  Forward(Box<RxJsonRpcMessage<RoleServer>>),
+  Reply(Value),
+  Ignore,
+}
+
+// Responses of a batch collected until the last one arrives
+#[derive(Default)]
+struct Batch {
+  // Ids of the requests handed to rmcp that are still unanswered
+  pending: HashSet<String>,
+  responses: Vec<Value>,
+}
+
+// Requests handed to rmcp, shared with the send futures
+#[derive(Default)]
+struct Outgoing {
+  // Ids of the requests not answered yet
+  in_flight: HashSet<String>,
+  // Ids of the requests the client cancelled before they were answered
+  cancelled: HashSet<String>,
+  batches: Vec<Batch>,
+}
+
+pub struct StdioTransport<R, W> {
+  lines: Lines<BufReader<R>>,
+  // Shared with the send futures, so lines never interleave
+  writer: Arc<tokio::sync::Mutex<W>>,
+  phase: SessionPhase,
+  // Messages of a batch not handed to rmcp yet
+  queued: VecDeque<RxJsonRpcMessage<RoleServer>>,
+  outgoing: Arc<Mutex<Outgoing>>,
+}
+
+impl<R, W> StdioTransport<R, W>
+where
+  R: AsyncRead + Unpin + Send + 'static,
+  W: AsyncWrite + Unpin + Send + 'static,
+{
+  pub fn new(reader: R, writer: W) -> Self {
+    Self {
+      lines: BufReader::new(reader).lines(),
+      writer: Arc::new(tokio::sync::Mutex::new(writer)),
+      phase: SessionPhase::Uninitialized,
+      queued: VecDeque::new(),
+      outgoing: Arc::default(),
+    }
+  }
+
+  // Handle one line of input; returns what has to be written back right away
+  fn read_line(&mut self, line: &str) -> Vec<Value> {
+    let message = match serde_json::from_str::<Value>(line) {
+      Ok(message) => message,
+      Err(e) => {
+        error!("MCP Server: Invalid JSON received: {}", e);
+        return vec![JsonRpcError::parse_error(e).to_response(Value::Null)];
+      }
+    };
+
+    let Value::Array(messages) = message else {
+      return match self.accept(message) {
+        Incoming::Forward(message) => self.forward(*message).into_iter().collect(),
+        Incoming::Reply(reply) => vec![reply],
+        Incoming::Ignore => Vec::new(),
+      };
+    };
+    if messages.is_empty() {
+      return vec![JsonRpcError::invalid_request("Empty batch").to_response(Value::Null)];
+    }
+
+    let mut replies = Vec::new();
+    let mut batch = Batch::default();
+    for message in messages {
+      match self.accept(message) {
+        Incoming::Forward(message) => {
+          if let JsonRpcMessage::Request(request) = message.as_ref() {
+            batch.pending.insert(id_key(&request.id));
+          }
+          replies.extend(self.forward(*message));
+        }
+        Incoming::Reply(reply) => batch.responses.push(reply),
+        Incoming::Ignore => {}
+      }
+    }
+    let mut outgoing = self.outgoing.lock().unwrap();
+    // Requests cancelled within the batch itself are never answered
+    batch
+      .pending
+      .retain(|key| !outgoing.cancelled.contains(key));
+    if !batch.pending.is_empty() {
+      outgoing.batches.push(batch);
+    } else if !batch.responses.is_empty() {
+      // A batch of notifications gets no reply at all
+      replies.push(Value::Array(batch.responses));
+    }
+    replies
+  }
+
+  // Queue a message for rmcp, keeping track of requests and their
+  // cancellation. Returns a batch the cancellation completed, if any.
+  fn forward(&mut self, message: RxJsonRpcMessage<RoleServer>) -> Option<Value> {
+    let mut completed = None;
+    match &message {
+      JsonRpcMessage::Request(request) => {
+        let mut outgoing = self.outgoing.lock().unwrap();
+        outgoing.in_flight.insert(id_key(&request.id));
+      }
+      JsonRpcMessage::Notification(notification) => {
+        if let ClientNotification::CancelledNotification(cancelled) = &notification.notification {
+          completed = cancel_request(&self.outgoing, &cancelled.params.request_id);
+        }
+      }
+      _ => {}
+    }
+    self.queued.push_back(message);
+    completed
+  }
+
+  fn accept(&mut self, message: Value) -> Incoming {
+    let id = message.get("id").cloned();
+    let message = match serde_json::from_value::<RxJsonRpcMessage<RoleServer>>(message) {
+      Ok(message) => message,
+      Err(e) => {
+        warn!("MCP Server: Invalid JSON-RPC message: {}", e);
+        let error = JsonRpcError::invalid_request("Expected a JSON-RPC 2.0 message");
+        return Incoming::Reply(error.to_response(id.unwrap_or(Value::Null)));
+      }
+    };
+    if self.phase == SessionPhase::Ready {
+      return match &message {
+        JsonRpcMessage::Request(request)
+          if matches!(request.request, ClientRequest::InitializeRequest(_)) =>
+        {
+          let error = JsonRpcError::invalid_request("Session is already initialized");
+          Incoming::Reply(error.to_response(json!(request.id)))
+        }
+        _ => Incoming::Forward(Box::new(message)),
+      };
+    }
+
+    // Only initialize and ping are allowed before the handshake has completed
+    match &message {
+      JsonRpcMessage::Request(request) => {
+        let id = json!(request.id);
+        let error = match &request.request {
+          ClientRequest::PingRequest(_) => return Incoming::Reply(result_response(id, json!({}))),
+          ClientRequest::InitializeRequest(_) if self.phase == SessionPhase::Uninitialized => {
+            self.phase = SessionPhase::Initializing;
+            return Incoming::Forward(Box::new(message));
+          }
+          ClientRequest::InitializeRequest(_) => {
+            JsonRpcError::invalid_request("Session is already initialized")
+          }
+          ClientRequest::CustomRequest(custom) if custom.method == "initialize" => {
+            JsonRpcError::invalid_params("Invalid initialize params")
+          }
+          _ => JsonRpcError::invalid_request("Server not initialized"),
+        };
+        Incoming::Reply(error.to_response(id))
+      }
+      JsonRpcMessage::Notification(notification)
+        if self.phase == SessionPhase::Initializing
+          && matches!(
+            notification.notification,
+            ClientNotification::InitializedNotification(_)
+          ) =>
+      {
+        self.phase = SessionPhase::Ready;
+        Incoming::Forward(Box::new(message))
+      }
+      _ => {
+        info!("MCP Server: Ignoring message before the session is initialized");
+        Incoming::Ignore
+      }
+    }
+  }
+}
+
+impl<R, W> Transport<RoleServer> for StdioTransport<R, W>
+where
+  R: AsyncRead + Unpin + Send + 'static,
+  W: AsyncWrite + Unpin + Send + 'static,
+{
+  type Error = io::Error;
+
+  fn send(
+    &mut self,
+    item: TxJsonRpcMessage<RoleServer>,
+  ) -> impl Future<Output = Result<(), Self::Error>> + Send + 'static {
+    let writer = self.writer.clone();
+    let message = serde_json::to_value(&item).map(|message| {
+      let id = match &item {
+        JsonRpcMessage::Response(response) => Some(&response.id),
+        JsonRpcMessage::Error(error) => Some(&error.id),
+        _ => None,
+      };
+      match id {
+        Some(id) => complete_request(&self.outgoing, id, message),
+        None => Some(message),
+      }
+    });
+    async move {
+      if let Some(message) = message? {
+        write_message(&writer, &message).await?;
+      }
+      Ok(())
+    }
+  }
+
+  async fn receive(&mut self) -> Option<RxJsonRpcMessage<RoleServer>> {
+    loop {
+      if let Some(message) = self.queued.pop_front() {
+        return Some(message);
+      }
+
+      let line = match self.lines.next_line().await {
+        Ok(Some(line)) => line,
+        Ok(None) => {
+          info!("MCP Server: EOF received, shutting down");
+          return None;
+        }
+        Err(e) => {
+          error!("MCP Server: Failed to read stdin: {}", e);
+          return None;
+        }
+      };
+      let line = line.trim();
+      if line.is_empty() {
+        continue;
+      }
+
+      for reply in self.read_line(line) {
+        if let Err(e) = write_message(&self.writer, &reply).await {
+          error!("MCP Server: Failed to write stdout: {}", e);
+          return None;
+        }
+      }
+    }
+  }
+
+  async fn close(&mut self) -> Result<(), Self::Error> {
+    self.writer.lock().await.flush().await
+  }
+}
+
+// Handle the response for `id`. Returns what to write: nothing when the
+// request was cancelled, the message itself when it isn't part of a batch,
+// the whole batch once it is complete, nothing otherwise.
+fn complete_request(outgoing: &Mutex<Outgoing>, id: &RequestId, message: Value) -> Option<Value> {
+  let key = id_key(id);
+  let mut outgoing = outgoing.lock().unwrap();
+  outgoing.in_flight.remove(&key);
+  if outgoing.cancelled.remove(&key) {
+    info!(
+      "MCP Server: Dropping the response to cancelled request {}",
+      key
+    );
+    return None;
+  }
+  let Some(index) = outgoing
+    .batches
+    .iter()
+    .position(|batch| batch.pending.contains(&key))
+  else {
+    return Some(message);
+  };
+
+  let batch = &mut outgoing.batches[index];
+  batch.pending.remove(&key);
+  batch.responses.push(message);
+  if !batch.pending.is_empty() {
+    return None;
+  }
+  Some(Value::Array(outgoing.batches.remove(index).responses))
+}
+
+// Mark an unanswered request as cancelled, so its response is dropped, and
+// stop waiting for it in its batch. Returns the batch if that was the last
+// response it waited for.
+fn cancel_request(outgoing: &Mutex<Outgoing>, id: &RequestId) -> Option<Value> {
+  let key = id_key(id);
+  let mut outgoing = outgoing.lock().unwrap();
+  // Already answered, or never seen
+  if !outgoing.in_flight.remove(&key) {
+    return None;
+  }
+  outgoing.cancelled.insert(key.clone());
+
+  let index = outgoing
+    .batches
+    .iter()
+    .position(|batch| batch.pending.contains(&key))?;
+  let batch = &mut outgoing.batches[index];
+  batch.pending.remove(&key);
+  if !batch.pending.is_empty() {
+    return None;
+  }
+  let responses = outgoing.batches.remove(index).responses;
+  (!responses.is_empty()).then_some(Value::Array(responses))
+}
+
+fn id_key(id: &RequestId) -> String {
+  json!(id).to_string()
+}
+
+async fn write_message<W: AsyncWrite + Unpin>(
+  writer: &tokio::sync::Mutex<W>,
+  message: &Value,
+) -> io::Result<()> {
+  let mut line = message.to_string();
+  line.push('\n');
+  let mut writer = writer.lock().await;
+  writer.write_all(line.as_bytes()).await?;
+  writer.flush().await
+}
@@ -0,0 +1,175 @@
+// Minimal server-sent events (text/event-stream) parser.
+// Bytes can be fed in arbitrary chunks; complete events are returned once the
+// blank line that terminates them has been seen.
+
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct SseEvent {
+  pub event: Option<String>,
+  pub id: Option<String>,
+  pub data: String,
+}
+
+#[derive(Debug, Default)]
+pub struct SseParser {
+  buffer: Vec<u8>,
+  current: SseEvent,
+  data_lines: Vec<String>,
+}
+
+impl SseParser {
+  pub fn new() -> Self {
+    Self::default()
+  }
+
+  pub fn feed(&mut self, chunk: &[u8]) -> Vec<SseEvent> {
+    self.buffer.extend_from_slice(chunk);
+
+    let mut events = Vec::new();
+    while let Some(pos) = self.buffer.iter().position(|&b| b == b'\n') {
+      let raw: Vec<u8> = self.buffer.drain(..=pos).collect();
+      let line = String::from_utf8_lossy(&raw);
+      let line = line.trim_end_matches(['\n', '\r']);
+
+      if line.is_empty() {
+        if let Some(event) = self.dispatch() {
+          events.push(event);
+        }
+        continue;
+      }
+
+      // Lines starting with ':' are comments, used as keep-alives
+      if line.starts_with(':') {
+        continue;
+      }
+
+      let (field, value) = match line.split_once(':') {
+        Some((field, value)) => (field, value.strip_prefix(' ').unwrap_or(value)),
+        None => (line, ""),
+      };
+      match field {
+        "data" => self.data_lines.push(value.to_string()),
+        "event" => self.current.event = Some(value.to_string()),
+        "id" => self.current.id = Some(value.to_string()),
+        _ => {}
+      }
+    }
+
+    events
+  }
+
+  // Flush an event left unterminated when the stream ended
+  pub fn finish(&mut self) -> Option<SseEvent> {
+    if !self.buffer.is_empty()
+      && let Some(event) = self.feed(b"\n").pop()
+    {
+      return Some(event);
+    }
+    self.dispatch()
+  }
+
+  fn dispatch(&mut self) -> Option<SseEvent> {
+    if self.data_lines.is_empty() {
+      self.current = SseEvent::default();
+      return None;
+    }
+    let mut event = std::mem::take(&mut self.current);
+    event.data = std::mem::take(&mut self.data_lines).join("\n");
+    Some(event)
+  }
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+
+  fn data(data: &str) -> SseEvent {
+    SseEvent {
+      data: data.to_string(),
+      ..Default::default()
+    }
+  }
+
+  // Every event, with the input fed in pieces of `size` bytes
+  fn parse_in_pieces(input: &str, size: usize) -> Vec<SseEvent> {
+    let mut parser = SseParser::new();
+    let mut events: Vec<_> = input
+      .as_bytes()
+      .chunks(size)
+      .flat_map(|chunk| parser.feed(chunk))
+      .collect();
+    events.extend(parser.finish());
+    events
+  }
+
+  #[test]
+  fn events_split_across_chunks() {
+    let input = "data: {\"a\":1}\n\ndata: 你好\n\n";
+    for size in 1..input.len() {
+      assert_eq!(
+        parse_in_pieces(input, size),
+        [data("{\"a\":1}"), data("你好")],
+        "chunks of {} bytes",
+        size
+      );
+    }
+  }
+
+  #[test]
+  fn events_wait_for_the_blank_line() {
+    let mut parser = SseParser::new();
+    assert!(parser.feed(b"data: a\n").is_empty());
+    assert!(parser.feed(b"data: b").is_empty());
+    assert_eq!(parser.feed(b"\n\n"), [data("a\nb")]);
+  }
+
+  #[test]
+  fn crlf_line_endings() {
+    let input = "event: message\r\nid: 7\r\ndata: a\r\n\r\ndata: b\r\n\r\n";
+    let first = SseEvent {
+      event: Some("message".to_string()),
+      id: Some("7".to_string()),
+      data: "a".to_string(),
+    };
+    assert_eq!(parse_in_pieces(input, 3), [first, data("b")]);
+  }
+
+  #[test]
+  fn comments_are_skipped() {
+    let input = ": keep-alive\n\ndata: a\n: in between\n\n:\n\n";
+    assert_eq!(parse_in_pieces(input, 64), [data("a")]);
+  }
+
+  #[test]
+  fn multi_line_data_is_joined() {
+    let input = "data: first\ndata:second\ndata\ndata:  indented\n\n";
+    assert_eq!(
+      parse_in_pieces(input, 64),
+      [data("first\nsecond\n\n indented")]
+    );
+  }
+
+  #[test]
+  fn unknown_fields_and_events_without_data_are_dropped() {
+    let input = "retry: 1000\n\nevent: ping\n\nfoo: bar\ndata: a\n\n";
+    assert_eq!(parse_in_pieces(input, 64), [data("a")]);
+  }
+
+  #[test]
+  fn finish_flushes_an_unterminated_event() {
+    // Without the blank line
+    let mut parser = SseParser::new();
+    assert!(parser.feed(b"data: [DONE]\n").is_empty());
+    assert_eq!(parser.finish(), Some(data("[DONE]")));
+    assert_eq!(parser.finish(), None);
+
+    // Without even the line ending
+    let mut parser = SseParser::new();
+    assert!(parser.feed(b"data: [DONE]").is_empty());
+    assert_eq!(parser.finish(), Some(data("[DONE]")));
+
+    // Nothing left over
+    let mut parser = SseParser::new();
+    assert_eq!(parser.feed(b"data: a\n\n"), [data("a")]);
+    assert_eq!(parser.finish(), None);
+  }
+}
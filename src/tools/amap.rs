@@ -1,5 +1,11 @@
+use async_trait::async_trait;
 use reqwest;
 use serde::{Deserialize, Serialize};
+use serde_json::{Value, json};
+
+use crate::mcp_registry::{BoxError, Tool, ToolContext, ToolOutput};
+use crate::tools::history::QueryHistory;
+use crate::tools::prompts::WEATHER_ADVISOR_PROMPT;
 
 #[derive(Debug, Serialize, Deserialize)]
 struct DistrictResponse {
@@ -49,9 +55,8 @@ pub struct Cast {
   pub nighttemp_float: String,
 }
 
-#[allow(dead_code)]
-pub async fn get_weather(location: &str, api_key: &str) -> Result<WeatherResponse, reqwest::Error> {
-  // 第一步：获取行政编码
+// 第一步：获取行政编码
+pub async fn lookup_adcode(location: &str, api_key: &str) -> Result<String, BoxError> {
   let district_url = format!(
     "https://restapi.amap.com/v3/config/district?key={}&keywords={}&subdistrict=0&extensions=all",
     api_key, location
@@ -59,14 +64,14 @@ pub async fn get_weather(location: &str, api_key: &str) -> Result<WeatherRespons
 
   let district_resp: DistrictResponse = reqwest::get(&district_url).await?.json().await?;
 
-  if district_resp.status != "1" || district_resp.districts.is_empty() {
-    eprintln!("行政编码查询失败");
-    return Err(reqwest::get("http://error").await.unwrap_err());
+  match district_resp.districts.into_iter().next() {
+    Some(district) if district_resp.status == "1" => Ok(district.adcode),
+    _ => Err(format!("行政编码查询失败: {}", location).into()),
   }
+}
 
-  let adcode = &district_resp.districts[0].adcode;
-
-  // 第二步：获取天气数据
+// 第二步：获取天气数据
+pub async fn get_forecast(adcode: &str, api_key: &str) -> Result<WeatherResponse, BoxError> {
   let weather_url = format!(
     "https://restapi.amap.com/v3/weather/weatherInfo?key={}&city={}&extensions=all&output=json",
     api_key, adcode
@@ -75,9 +80,92 @@ pub async fn get_weather(location: &str, api_key: &str) -> Result<WeatherRespons
   let weather_resp: WeatherResponse = reqwest::get(&weather_url).await?.json().await?;
 
   if weather_resp.status != "1" {
-    eprintln!("天气查询失败");
-    return Err(reqwest::get("http://error").await.unwrap_err());
+    return Err(format!("天气查询失败: {}", weather_resp.info).into());
   }
 
   Ok(weather_resp)
 }
+
+pub async fn get_weather(location: &str, api_key: &str) -> Result<WeatherResponse, BoxError> {
+  let adcode = lookup_adcode(location, api_key).await?;
+  get_forecast(&adcode, api_key).await
+}
+
+// MCP tool: weather forecast for a city
+pub struct WeatherTool {
+  api_key: String,
+  history: Option<QueryHistory>,
+}
+
+impl WeatherTool {
+  pub fn new(api_key: impl Into<String>) -> Self {
+    Self {
+      api_key: api_key.into(),
+      history: None,
+    }
+  }
+
+  // Record every queried location in `history`
+  pub fn with_history(mut self, history: QueryHistory) -> Self {
+    self.history = Some(history);
+    self
+  }
+
+  async fn forecast(&self, location: &str, context: &ToolContext) -> Result<ToolOutput, BoxError> {
+    context.progress(0.0, Some(2.0), Some("查询行政编码"));
+    let adcode = match lookup_adcode(location, &self.api_key).await {
+      Ok(adcode) => adcode,
+      Err(e) => return Ok(ToolOutput::error(format!("Weather API error: {}", e))),
+    };
+
+    context.progress(1.0, Some(2.0), Some("查询天气预报"));
+    let weather_info = match get_forecast(&adcode, &self.api_key).await {
+      Ok(weather_info) => weather_info,
+      Err(e) => return Ok(ToolOutput::error(format!("Weather API error: {}", e))),
+    };
+
+    context.progress(2.0, Some(2.0), Some("天气信息获取成功"));
+    let content = serde_json::to_string_pretty(&weather_info)?;
+    Ok(ToolOutput::text(format!("天气信息获取成功：\n{}", content)))
+  }
+}
+
+#[async_trait]
+impl Tool for WeatherTool {
+  fn name(&self) -> &str {
+    "get_weather"
+  }
+
+  fn description(&self) -> &str {
+    "获取指定城市的天气预报信息"
+  }
+
+  fn input_schema(&self) -> Value {
+    json!({
+      "type": "object",
+      "properties": {
+        "location": {
+          "type": "string",
+          "description": "城市名称，例如：上海"
+        }
+      },
+      "required": ["location"]
+    })
+  }
+
+  fn system_prompt(&self) -> Option<&str> {
+    Some(WEATHER_ADVISOR_PROMPT)
+  }
+
+  async fn execute(&self, arguments: Value, context: ToolContext) -> Result<ToolOutput, BoxError> {
+    let location = arguments["location"]
+      .as_str()
+      .ok_or("Missing location parameter")?;
+
+    let output = self.forecast(location, &context).await;
+    if let Some(history) = &self.history {
+      history.record_output(location, &output);
+    }
+    output
+  }
+}
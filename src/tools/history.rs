@@ -0,0 +1,209 @@
+use serde::{Deserialize, Serialize};
+use serde_json::json;
+use std::collections::VecDeque;
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::sync::mpsc;
+use std::sync::{Arc, Mutex};
+use std::thread::{self, JoinHandle};
+use std::time::{SystemTime, UNIX_EPOCH};
+use tracing::warn;
+
+use crate::mcp_registry::{BoxError, ToolOutput};
+
+// Default number of queries kept per history
+pub const DEFAULT_HISTORY_SIZE: usize = 50;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum QueryStatus {
+  Ok,
+  Error,
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct QueryRecord {
+  pub query: String,
+  // Seconds since the Unix epoch
+  pub timestamp: u64,
+  pub status: QueryStatus,
+  #[serde(default, skip_serializing_if = "Option::is_none")]
+  pub error: Option<String>,
+}
+
+// Called with every new record
+pub type HistoryListener = Arc<dyn Fn(&QueryRecord) + Send + Sync>;
+
+// Bounded history of the queries a tool has run, oldest dropped first.
+// With a file it is loaded on start and rewritten after every query, so it
+// survives restarts. Clones share the same history.
+#[derive(Clone)]
+pub struct QueryHistory {
+  records: Arc<Mutex<VecDeque<QueryRecord>>>,
+  capacity: usize,
+  writer: Option<Arc<HistoryWriter>>,
+  listener: Option<HistoryListener>,
+}
+
+// Rewrites the history file on its own thread, so recording a query never
+// waits for the file system. Snapshots are written in the order they were
+// taken; when several are waiting only the newest is written. Dropping the
+// writer waits for the last snapshot to be written.
+struct HistoryWriter {
+  snapshots: Option<mpsc::Sender<Vec<QueryRecord>>>,
+  thread: Option<JoinHandle<()>>,
+}
+
+impl HistoryWriter {
+  fn spawn(path: PathBuf) -> Self {
+    let (snapshots, receiver) = mpsc::channel::<Vec<QueryRecord>>();
+    let thread = thread::Builder::new()
+      .name("query-history".to_string())
+      .spawn(move || {
+        while let Ok(mut records) = receiver.recv() {
+          while let Ok(newer) = receiver.try_recv() {
+            records = newer;
+          }
+          if let Err(e) = save(&path, &records) {
+            warn!("Query history: Could not save {}: {}", path.display(), e);
+          }
+        }
+      });
+    match thread {
+      Ok(thread) => Self {
+        snapshots: Some(snapshots),
+        thread: Some(thread),
+      },
+      Err(e) => {
+        warn!("Query history: Could not start the writer: {}", e);
+        Self {
+          snapshots: None,
+          thread: None,
+        }
+      }
+    }
+  }
+
+  fn write(&self, records: Vec<QueryRecord>) {
+    if let Some(snapshots) = &self.snapshots {
+      // Only fails once the thread is gone, which has been logged
+      let _ = snapshots.send(records);
+    }
+  }
+}
+
+impl Drop for HistoryWriter {
+  fn drop(&mut self) {
+    // Closing the channel ends the thread after the last write
+    self.snapshots.take();
+    if let Some(thread) = self.thread.take() {
+      let _ = thread.join();
+    }
+  }
+}
+
+impl QueryHistory {
+  // In-memory history of at most `capacity` queries (at least 1)
+  pub fn new(capacity: usize) -> Self {
+    Self {
+      records: Arc::default(),
+      capacity: capacity.max(1),
+      writer: None,
+      listener: None,
+    }
+  }
+
+  // Call `listener` after every recorded query
+  pub fn with_listener(mut self, listener: impl Fn(&QueryRecord) + Send + Sync + 'static) -> Self {
+    self.listener = Some(Arc::new(listener));
+    self
+  }
+
+  // History persisted to `path`. An unreadable file is logged and replaced.
+  pub fn open(path: impl Into<PathBuf>, capacity: usize) -> Self {
+    let path = path.into();
+    let mut history = Self::new(capacity);
+    match load(&path) {
+      Ok(records) => {
+        let mut records: VecDeque<_> = records.into();
+        while records.len() > history.capacity {
+          records.pop_front();
+        }
+        history.records = Arc::new(Mutex::new(records));
+      }
+      Err(e) => warn!("Query history: Could not load {}: {}", path.display(), e),
+    }
+    history.writer = Some(Arc::new(HistoryWriter::spawn(path)));
+    history
+  }
+
+  pub fn record(&self, query: &str, status: QueryStatus, error: Option<String>) {
+    let timestamp = SystemTime::now()
+      .duration_since(UNIX_EPOCH)
+      .map(|elapsed| elapsed.as_secs())
+      .unwrap_or_default();
+
+    let record = QueryRecord {
+      query: query.to_string(),
+      timestamp,
+      status,
+      error,
+    };
+    let mut records = self.records.lock().unwrap();
+    records.push_back(record.clone());
+    while records.len() > self.capacity {
+      records.pop_front();
+    }
+
+    // Handed to the writer under the lock so concurrent queries can't
+    // reorder the file; the lock is not held while writing
+    if let Some(writer) = &self.writer {
+      writer.write(records.iter().cloned().collect());
+    }
+    drop(records);
+
+    if let Some(listener) = &self.listener {
+      listener(&record);
+    }
+  }
+
+  // Record the outcome of a tool call for `query`
+  pub fn record_output(&self, query: &str, output: &Result<ToolOutput, BoxError>) {
+    match output {
+      Ok(output) if !output.is_error => self.record(query, QueryStatus::Ok, None),
+      Ok(output) => self.record(query, QueryStatus::Error, Some(output.text.clone())),
+      Err(e) => self.record(query, QueryStatus::Error, Some(e.to_string())),
+    }
+  }
+
+  // Newest first
+  pub fn recent(&self) -> Vec<QueryRecord> {
+    self.records.lock().unwrap().iter().rev().cloned().collect()
+  }
+
+  // Resource contents: `{"recent_queries": [...]}`, newest first
+  pub fn to_json(&self) -> String {
+    json!({ "recent_queries": self.recent() }).to_string()
+  }
+}
+
+fn load(path: &Path) -> Result<Vec<QueryRecord>, BoxError> {
+  if !path.exists() {
+    return Ok(Vec::new());
+  }
+  Ok(serde_json::from_str(&fs::read_to_string(path)?)?)
+}
+
+// Write a temporary file and rename it over the old one, so a crash never
+// leaves a half-written history behind
+fn save(path: &Path, records: &[QueryRecord]) -> Result<(), BoxError> {
+  if let Some(dir) = path.parent()
+    && !dir.as_os_str().is_empty()
+  {
+    fs::create_dir_all(dir)?;
+  }
+  let tmp = path.with_extension("json.tmp");
+  fs::write(&tmp, serde_json::to_string_pretty(records)?)?;
+  fs::rename(&tmp, path)?;
+  Ok(())
+}
@@ -1,2 +1,103 @@
 pub mod amap;
-pub mod serper;
\ No newline at end of file
+pub mod serper;
+pub mod prompts;
+pub mod resources;
+pub mod history;
+
+use percent_encoding::{AsciiSet, NON_ALPHANUMERIC, utf8_percent_encode};
+use std::path::Path;
+
+use crate::mcp_registry::{McpRegistry, ResourceUpdates};
+use history::{DEFAULT_HISTORY_SIZE, QueryHistory, QueryRecord, QueryStatus};
+
+// Weather and search tools with their prompts, resources and resource
+// templates. The tools record their queries for the recent-queries
+// resources, kept in `history_dir` across restarts when given.
+pub fn default_registry(
+  amap_key: &str,
+  serper_key: &str,
+  history_dir: Option<&Path>,
+) -> McpRegistry {
+  let history = |file: &str| match history_dir {
+    Some(dir) => QueryHistory::open(dir.join(file), DEFAULT_HISTORY_SIZE),
+    None => QueryHistory::new(DEFAULT_HISTORY_SIZE),
+  };
+  let updates = ResourceUpdates::new();
+  let weather_history = history("weather-queries.json").with_listener(announce_query(
+    &updates,
+    "weather://recent-queries",
+    "weather://forecast/",
+  ));
+  let search_history = history("search-queries.json").with_listener(announce_query(
+    &updates,
+    "search://recent-queries",
+    "search://results/",
+  ));
+
+  McpRegistry::new()
+    .with_resource_updates(updates)
+    .with_tool(amap::WeatherTool::new(amap_key).with_history(weather_history.clone()))
+    .with_tool(serper::SearchTool::new(serper_key).with_history(search_history.clone()))
+    .with_prompt(prompts::WeatherAdvisorPrompt)
+    .with_prompt(prompts::SearchAnalyzerPrompt)
+    .with_resource(resources::RecentWeatherQueries::new(weather_history))
+    .with_resource(resources::RecentSearchQueries::new(search_history))
+    .with_resource_template(resources::WeatherForecastTemplate::new(amap_key))
+    .with_resource_template(resources::SearchResultsTemplate::new(serper_key))
+}
+
+// Everything but RFC 3986 unreserved characters is escaped, so a query is
+// always one URI template variable and decodes back to itself
+const QUERY_ENCODE_SET: &AsciiSet = &NON_ALPHANUMERIC
+  .remove(b'-')
+  .remove(b'.')
+  .remove(b'_')
+  .remove(b'~');
+
+// A recorded query changes the recent-queries resource and, when it
+// succeeded, means fresh data for the matching live resource
+fn announce_query(
+  updates: &ResourceUpdates,
+  recent_uri: &'static str,
+  live_prefix: &'static str,
+) -> impl Fn(&QueryRecord) + Send + Sync + 'static {
+  let updates = updates.clone();
+  move |record| {
+    updates.publish(recent_uri);
+    if record.status == QueryStatus::Ok {
+      let query = utf8_percent_encode(&record.query, QUERY_ENCODE_SET);
+      updates.publish(format!("{}{}", live_prefix, query));
+    }
+  }
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+  use crate::mcp_registry::match_uri_template;
+
+  #[test]
+  fn live_resource_uris_are_percent_encoded() {
+    let updates = ResourceUpdates::new();
+    let mut published = updates.subscribe();
+    let announce = announce_query(&updates, "search://recent-queries", "search://results/");
+
+    let query = "rust 2024/edition?q=a&b#1 北京";
+    announce(&QueryRecord {
+      query: query.to_string(),
+      timestamp: 0,
+      status: QueryStatus::Ok,
+      error: None,
+    });
+    assert_eq!(published.try_recv().unwrap(), "search://recent-queries");
+    let uri = published.try_recv().unwrap();
+    assert_eq!(
+      uri,
+      "search://results/rust%202024%2Fedition%3Fq%3Da%26b%231%20%E5%8C%97%E4%BA%AC"
+    );
+
+    // Template matching decodes it back to the query
+    let arguments = match_uri_template("search://results/{query}", &uri).unwrap();
+    assert_eq!(arguments["query"], query);
+  }
+}
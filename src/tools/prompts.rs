@@ -0,0 +1,90 @@
+use serde_json::Value;
+
+use crate::mcp_registry::{BoxError, Prompt, PromptArgument, PromptMessage, PromptResult};
+
+pub const WEATHER_ADVISOR_PROMPT: &str = "你是一个专业的天气顾问，请根据获取到的天气数据给出详细的穿衣建议。注意：\n1. 分析温度范围和温差\n2. 考虑天气现象（晴、阴、雨等）\n3. 考虑风力大小\n4. 给出具体的穿衣层次建议\n5. 如有必要，提醒是否需要携带雨具或防晒用品";
+
+pub const SEARCH_ANALYZER_PROMPT: &str =
+  "你是一个专业的信息分析师，请根据搜索结果给出准确、简洁的回答。";
+
+// 天气顾问提示模板
+pub struct WeatherAdvisorPrompt;
+
+impl Prompt for WeatherAdvisorPrompt {
+  fn name(&self) -> &str {
+    "weather_advisor"
+  }
+
+  fn description(&self) -> &str {
+    "专业的天气顾问提示模板"
+  }
+
+  fn arguments(&self) -> Vec<PromptArgument> {
+    vec![PromptArgument {
+      name: "weather_data".to_string(),
+      description: "天气数据JSON".to_string(),
+      required: true,
+    }]
+  }
+
+  fn get(&self, arguments: &Value) -> Result<PromptResult, BoxError> {
+    let weather_data = arguments["weather_data"]
+      .as_str()
+      .ok_or("Missing weather_data parameter")?;
+
+    Ok(PromptResult {
+      description: "天气顾问专业分析".to_string(),
+      messages: vec![
+        PromptMessage {
+          role: "system".to_string(),
+          text: WEATHER_ADVISOR_PROMPT.to_string(),
+        },
+        PromptMessage {
+          role: "user".to_string(),
+          text: format!("请分析以下天气数据并给出建议：\n{}", weather_data),
+        },
+      ],
+    })
+  }
+}
+
+// 搜索结果分析师提示模板
+pub struct SearchAnalyzerPrompt;
+
+impl Prompt for SearchAnalyzerPrompt {
+  fn name(&self) -> &str {
+    "search_analyzer"
+  }
+
+  fn description(&self) -> &str {
+    "搜索结果分析师提示模板"
+  }
+
+  fn arguments(&self) -> Vec<PromptArgument> {
+    vec![PromptArgument {
+      name: "search_results".to_string(),
+      description: "搜索结果数据".to_string(),
+      required: true,
+    }]
+  }
+
+  fn get(&self, arguments: &Value) -> Result<PromptResult, BoxError> {
+    let search_results = arguments["search_results"]
+      .as_str()
+      .ok_or("Missing search_results parameter")?;
+
+    Ok(PromptResult {
+      description: "搜索结果专业分析".to_string(),
+      messages: vec![
+        PromptMessage {
+          role: "system".to_string(),
+          text: SEARCH_ANALYZER_PROMPT.to_string(),
+        },
+        PromptMessage {
+          role: "user".to_string(),
+          text: format!("请分析以下搜索结果：\n{}", search_results),
+        },
+      ],
+    })
+  }
+}
@@ -0,0 +1,134 @@
+use async_trait::async_trait;
+
+use crate::mcp_registry::{BoxError, Resource, ResourceTemplate, TemplateArguments};
+use crate::tools::amap::get_weather;
+use crate::tools::history::QueryHistory;
+use crate::tools::serper::search_with_key;
+
+// weather://recent-queries, served from the get_weather history
+pub struct RecentWeatherQueries {
+  history: QueryHistory,
+}
+
+impl RecentWeatherQueries {
+  pub fn new(history: QueryHistory) -> Self {
+    Self { history }
+  }
+}
+
+#[async_trait]
+impl Resource for RecentWeatherQueries {
+  fn uri(&self) -> &str {
+    "weather://recent-queries"
+  }
+
+  fn name(&self) -> &str {
+    "Recent Weather Queries"
+  }
+
+  fn description(&self) -> &str {
+    "Recently queried weather locations"
+  }
+
+  async fn read(&self) -> Result<String, BoxError> {
+    Ok(self.history.to_json())
+  }
+}
+
+// search://recent-queries, served from the search history
+pub struct RecentSearchQueries {
+  history: QueryHistory,
+}
+
+impl RecentSearchQueries {
+  pub fn new(history: QueryHistory) -> Self {
+    Self { history }
+  }
+}
+
+#[async_trait]
+impl Resource for RecentSearchQueries {
+  fn uri(&self) -> &str {
+    "search://recent-queries"
+  }
+
+  fn name(&self) -> &str {
+    "Recent Search Queries"
+  }
+
+  fn description(&self) -> &str {
+    "Recently performed search queries"
+  }
+
+  async fn read(&self) -> Result<String, BoxError> {
+    Ok(self.history.to_json())
+  }
+}
+
+// weather://forecast/{city}, live forecast through the AMap API
+pub struct WeatherForecastTemplate {
+  api_key: String,
+}
+
+impl WeatherForecastTemplate {
+  pub fn new(api_key: impl Into<String>) -> Self {
+    Self {
+      api_key: api_key.into(),
+    }
+  }
+}
+
+#[async_trait]
+impl ResourceTemplate for WeatherForecastTemplate {
+  fn uri_template(&self) -> &str {
+    "weather://forecast/{city}"
+  }
+
+  fn name(&self) -> &str {
+    "Weather Forecast"
+  }
+
+  fn description(&self) -> &str {
+    "Weather forecast for a city, e.g. weather://forecast/上海"
+  }
+
+  async fn read(&self, arguments: TemplateArguments) -> Result<String, BoxError> {
+    let city = arguments.get("city").ok_or("Missing city")?;
+    let weather = get_weather(city, &self.api_key).await?;
+    Ok(serde_json::to_string_pretty(&weather)?)
+  }
+}
+
+// search://results/{query}, live Google results through Serper
+pub struct SearchResultsTemplate {
+  api_key: String,
+}
+
+impl SearchResultsTemplate {
+  pub fn new(api_key: impl Into<String>) -> Self {
+    Self {
+      api_key: api_key.into(),
+    }
+  }
+}
+
+#[async_trait]
+impl ResourceTemplate for SearchResultsTemplate {
+  fn uri_template(&self) -> &str {
+    "search://results/{query}"
+  }
+
+  fn name(&self) -> &str {
+    "Search Results"
+  }
+
+  fn description(&self) -> &str {
+    "Google search results for a percent-encoded query, e.g. search://results/MCP%20protocol"
+  }
+
+  async fn read(&self, arguments: TemplateArguments) -> Result<String, BoxError> {
+    let query = arguments.get("query").ok_or("Missing query")?;
+    let results = search_with_key(query, &self.api_key).await?;
+    Ok(serde_json::to_string_pretty(&results)?)
+  }
+}
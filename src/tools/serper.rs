@@ -1,7 +1,13 @@
+use async_trait::async_trait;
 use reqwest;
 use serde::{Deserialize, Serialize};
+use serde_json::{Value, json};
 use std::env;
 
+use crate::mcp_registry::{BoxError, Tool, ToolContext, ToolOutput};
+use crate::tools::history::QueryHistory;
+use crate::tools::prompts::SEARCH_ANALYZER_PROMPT;
+
 #[derive(Debug, Serialize, Deserialize)]
 pub struct SearchResult {
   pub title: String,
@@ -31,7 +37,10 @@ pub struct OrganicResult {
   pub position: i32,
 }
 
-pub async fn search_with_key(query: &str, api_key: &str) -> Result<Vec<SearchResult>, reqwest::Error> {
+pub async fn search_with_key(
+  query: &str,
+  api_key: &str,
+) -> Result<Vec<SearchResult>, reqwest::Error> {
   let client = reqwest::Client::new();
 
   let response = client
@@ -61,7 +70,6 @@ pub async fn search_with_key(query: &str, api_key: &str) -> Result<Vec<SearchRes
 }
 
 // Backward compatibility function
-#[allow(dead_code)]
 pub async fn search(query: &str) -> Result<Vec<SearchResult>, reqwest::Error> {
   dotenv::dotenv().ok();
   let api_key = env::var("SERPER_API_KEY").expect("SERPER_API_KEY must be set");
@@ -69,7 +77,6 @@ pub async fn search(query: &str) -> Result<Vec<SearchResult>, reqwest::Error> {
 }
 
 // 将搜索结果格式化为易读的字符串
-#[allow(dead_code)]
 pub fn format_results(results: &[SearchResult], max_results: usize) -> String {
   let results = results
     .iter()
@@ -93,3 +100,77 @@ pub fn format_results(results: &[SearchResult], max_results: usize) -> String {
     results
   }
 }
+
+// MCP tool: Google search through Serper
+pub struct SearchTool {
+  api_key: String,
+  history: Option<QueryHistory>,
+}
+
+impl SearchTool {
+  pub fn new(api_key: impl Into<String>) -> Self {
+    Self {
+      api_key: api_key.into(),
+      history: None,
+    }
+  }
+
+  // Record every search query in `history`
+  pub fn with_history(mut self, history: QueryHistory) -> Self {
+    self.history = Some(history);
+    self
+  }
+
+  async fn search(&self, query: &str) -> Result<ToolOutput, BoxError> {
+    match search_with_key(query, &self.api_key).await {
+      Ok(search_results) => {
+        let formatted_results = format_results(&search_results, 3);
+        Ok(ToolOutput::text(format!(
+          "搜索结果：\n{}",
+          formatted_results
+        )))
+      }
+      Err(e) => Ok(ToolOutput::error(format!("Search API error: {}", e))),
+    }
+  }
+}
+
+#[async_trait]
+impl Tool for SearchTool {
+  fn name(&self) -> &str {
+    "search"
+  }
+
+  fn description(&self) -> &str {
+    "使用Google搜索获取实时信息"
+  }
+
+  fn input_schema(&self) -> Value {
+    json!({
+      "type": "object",
+      "properties": {
+        "query": {
+          "type": "string",
+          "description": "搜索查询词"
+        }
+      },
+      "required": ["query"]
+    })
+  }
+
+  fn system_prompt(&self) -> Option<&str> {
+    Some(SEARCH_ANALYZER_PROMPT)
+  }
+
+  async fn execute(&self, arguments: Value, _context: ToolContext) -> Result<ToolOutput, BoxError> {
+    let query = arguments["query"]
+      .as_str()
+      .ok_or("Missing query parameter")?;
+
+    let output = self.search(query).await;
+    if let Some(history) = &self.history {
+      history.record_output(query, &output);
+    }
+    output
+  }
+}
@@ -0,0 +1,205 @@
+// Agent::run against a stub chat completions endpoint and an in-process MCP
+// server with one echo tool
+
+use async_trait::async_trait;
+use axum::extract::State;
+use axum::response::{IntoResponse, Response};
+use axum::routing::post;
+use axum::{Json, Router};
+use deepseek_agent::agent::{Agent, AgentError, DEFAULT_SYSTEM_PROMPT};
+use deepseek_agent::deepseek::DeepSeekClient;
+use deepseek_agent::mcp_client::McpClient;
+use deepseek_agent::mcp_host::McpHost;
+use deepseek_agent::mcp_registry::{BoxError, McpRegistry, Tool, ToolContext, ToolOutput};
+use deepseek_agent::mcp_server_simple::SimpleMcpServer;
+use serde_json::{Value, json};
+use std::sync::{Arc, Mutex};
+
+struct EchoTool;
+
+#[async_trait]
+impl Tool for EchoTool {
+  fn name(&self) -> &str {
+    "echo"
+  }
+
+  fn description(&self) -> &str {
+    "Echo the text back"
+  }
+
+  fn input_schema(&self) -> Value {
+    json!({
+      "type": "object",
+      "properties": {
+        "text": {"type": "string"}
+      },
+      "required": ["text"]
+    })
+  }
+
+  async fn execute(&self, arguments: Value, _context: ToolContext) -> Result<ToolOutput, BoxError> {
+    Ok(ToolOutput::text(
+      arguments["text"].as_str().unwrap_or_default(),
+    ))
+  }
+}
+
+// Answers the n-th request with the n-th message, the last one over and
+// over, and keeps every request body
+#[derive(Clone)]
+struct Stub {
+  messages: Arc<Vec<Value>>,
+  requests: Arc<Mutex<Vec<Value>>>,
+}
+
+async fn chat_completions(State(stub): State<Stub>, Json(request): Json<Value>) -> Response {
+  let turn = {
+    let mut requests = stub.requests.lock().unwrap();
+    requests.push(request.clone());
+    requests.len() - 1
+  };
+  let message = &stub.messages[turn.min(stub.messages.len() - 1)];
+  let finish_reason = if message["tool_calls"].is_array() {
+    "tool_calls"
+  } else {
+    "stop"
+  };
+
+  if request["stream"] != true {
+    return Json(json!({
+      "id": format!("response-{}", turn),
+      "model": "stub",
+      "choices": [{"index": 0, "message": message, "finish_reason": finish_reason}]
+    }))
+    .into_response();
+  }
+
+  // The whole message as one delta; tool call fragments carry their index
+  let mut delta = message.clone();
+  if let Some(calls) = delta["tool_calls"].as_array_mut() {
+    for (index, call) in calls.iter_mut().enumerate() {
+      call["index"] = json!(index);
+    }
+  }
+  let chunk = json!({
+    "id": format!("response-{}", turn),
+    "choices": [{"index": 0, "delta": delta, "finish_reason": finish_reason}]
+  });
+  let body = format!("data: {}\n\ndata: [DONE]\n\n", chunk);
+  ([("content-type", "text/event-stream")], body).into_response()
+}
+
+async fn start_stub(messages: Vec<Value>) -> (String, Arc<Mutex<Vec<Value>>>) {
+  let requests = Arc::new(Mutex::new(Vec::new()));
+  let stub = Stub {
+    messages: Arc::new(messages),
+    requests: requests.clone(),
+  };
+  let app = Router::new()
+    .route("/chat/completions", post(chat_completions))
+    .with_state(stub);
+  let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await.unwrap();
+  let url = format!("http://{}/chat/completions", listener.local_addr().unwrap());
+  tokio::spawn(async move { axum::serve(listener, app).await });
+  (url, requests)
+}
+
+async fn echo_host() -> McpHost {
+  let server = SimpleMcpServer::with_registry(McpRegistry::new().with_tool(EchoTool));
+  let (client_io, server_io) = tokio::io::duplex(64 * 1024);
+  tokio::spawn(async move {
+    let (reader, writer) = tokio::io::split(server_io);
+    let _ = server.run_stdio_with(reader, writer).await;
+  });
+  let mut host = McpHost::new();
+  host
+    .add_client("local", McpClient::with_transport(client_io).await.unwrap())
+    .await
+    .unwrap();
+  host
+}
+
+fn echo_call() -> Value {
+  json!({
+    "role": "assistant",
+    "content": "",
+    "reasoning_content": "先调用工具",
+    "tool_calls": [{
+      "id": "call_1",
+      "type": "function",
+      "function": {"name": "echo", "arguments": "{\"text\": \"晴\"}"}
+    }]
+  })
+}
+
+#[tokio::test]
+async fn tool_results_are_sent_back_with_the_history() {
+  for streaming in [false, true] {
+    let final_answer = json!({
+      "role": "assistant",
+      "content": "上海今天晴",
+      "reasoning_content": "工具说晴"
+    });
+    let (url, requests) = start_stub(vec![echo_call(), final_answer]).await;
+    let mut agent = Agent::new(DeepSeekClient::new("key", url, "stub"), echo_host().await);
+
+    let reply = if streaming {
+      agent.run_streaming("上海天气", &mut |_| {}).await.unwrap()
+    } else {
+      agent.run("上海天气").await.unwrap()
+    };
+    assert_eq!(reply.answer, "上海今天晴");
+    assert_eq!(reply.reasoning.as_deref(), Some("先调用工具\n\n工具说晴"));
+
+    let requests = requests.lock().unwrap();
+    assert_eq!(requests.len(), 2);
+    assert_eq!(requests[0]["tools"][0]["function"]["name"], "echo");
+    assert_eq!(requests[0]["messages"].as_array().unwrap().len(), 2);
+    // History replayed, then the tool call without its reasoning, then the result
+    assert_eq!(
+      requests[1]["messages"],
+      json!([
+        {"role": "system", "content": DEFAULT_SYSTEM_PROMPT},
+        {"role": "user", "content": "上海天气"},
+        {
+          "role": "assistant",
+          "content": "",
+          "tool_calls": [{
+            "id": "call_1",
+            "type": "function",
+            "function": {"name": "echo", "arguments": "{\"text\": \"晴\"}"}
+          }]
+        },
+        {"role": "tool", "content": "晴", "tool_call_id": "call_1"}
+      ]),
+      "streaming: {}",
+      streaming
+    );
+    assert!(!requests[1].to_string().contains("reasoning_content"));
+  }
+}
+
+#[tokio::test]
+async fn endless_tool_calls_stop_at_the_iteration_cap() {
+  let (url, requests) = start_stub(vec![echo_call()]).await;
+  let mut agent =
+    Agent::new(DeepSeekClient::new("key", url, "stub"), echo_host().await).with_max_iterations(3);
+
+  let error = agent.run("上海天气").await.unwrap_err();
+  assert!(
+    matches!(
+      error.downcast_ref::<AgentError>(),
+      Some(AgentError::MaxIterationsExceeded(3))
+    ),
+    "{}",
+    error
+  );
+  assert_eq!(requests.lock().unwrap().len(), 3);
+  // Every call was answered before the next request
+  let tool_results = agent
+    .messages()
+    .iter()
+    .filter(|message| message.tool_call_id.as_deref() == Some("call_1"))
+    .count();
+  assert_eq!(tool_results, 3);
+}
@@ -0,0 +1,271 @@
+// McpHost over in-process rmcp servers connected through in-memory pipes
+
+use deepseek_agent::mcp_client::McpClient;
+use deepseek_agent::mcp_host::{MAX_TOOL_NAME_LEN, McpHost};
+use rmcp::model::{
+  CallToolRequestParams, CallToolResult, Content, ErrorData, ListToolsResult,
+  PaginatedRequestParams, ServerCapabilities, ServerInfo, Tool,
+};
+use rmcp::service::{Peer, RequestContext, RoleServer};
+use rmcp::{ServerHandler, ServiceExt};
+use serde_json::{Map, json};
+use std::sync::{Arc, Mutex};
+use std::time::Duration;
+
+// Server offering tools by name that answer with `server/tool`. Without a
+// tool list, tools/list fails. Clones share the tool list.
+#[derive(Clone)]
+struct ToolServer {
+  name: &'static str,
+  tools: Arc<Mutex<Option<Vec<&'static str>>>>,
+}
+
+impl ToolServer {
+  fn new(name: &'static str, tools: &[&'static str]) -> Self {
+    Self {
+      name,
+      tools: Arc::new(Mutex::new(Some(tools.to_vec()))),
+    }
+  }
+
+  fn broken(name: &'static str) -> Self {
+    Self {
+      name,
+      tools: Arc::default(),
+    }
+  }
+
+  fn set_tools(&self, tools: Option<&[&'static str]>) {
+    *self.tools.lock().unwrap() = tools.map(<[_]>::to_vec);
+  }
+}
+
+impl ServerHandler for ToolServer {
+  fn get_info(&self) -> ServerInfo {
+    ServerInfo {
+      capabilities: ServerCapabilities::builder().enable_tools().build(),
+      ..Default::default()
+    }
+  }
+
+  async fn list_tools(
+    &self,
+    _request: Option<PaginatedRequestParams>,
+    _context: RequestContext<RoleServer>,
+  ) -> Result<ListToolsResult, ErrorData> {
+    let tools = self
+      .tools
+      .lock()
+      .unwrap()
+      .clone()
+      .ok_or_else(|| ErrorData::internal_error("tools unavailable", None))?;
+    Ok(ListToolsResult {
+      tools: tools
+        .iter()
+        .map(|name| Tool::new(*name, "test tool", Arc::new(Map::new())))
+        .collect(),
+      ..Default::default()
+    })
+  }
+
+  async fn call_tool(
+    &self,
+    request: CallToolRequestParams,
+    _context: RequestContext<RoleServer>,
+  ) -> Result<CallToolResult, ErrorData> {
+    let text = format!("{}/{}", self.name, request.name);
+    Ok(CallToolResult::success(vec![Content::text(text)]))
+  }
+}
+
+async fn connect(server: impl ServerHandler) -> McpClient {
+  connect_with_peer(server).await.0
+}
+
+// Also hands back the server side, to send notifications from
+async fn connect_with_peer(server: impl ServerHandler) -> (McpClient, Peer<RoleServer>) {
+  let (client_io, server_io) = tokio::io::duplex(4096);
+  let serving = tokio::spawn(async move { server.serve(server_io).await });
+  let client = McpClient::with_transport(client_io).await.unwrap();
+  let service = serving.await.unwrap().unwrap();
+  let peer = service.peer().clone();
+  tokio::spawn(async move {
+    let _ = service.waiting().await;
+  });
+  (client, peer)
+}
+
+fn tool_names(host: &McpHost) -> Vec<&str> {
+  host
+    .list_tools()
+    .iter()
+    .filter_map(|tool| tool["name"].as_str())
+    .collect()
+}
+
+#[tokio::test]
+async fn servers_whose_tools_cannot_be_listed_are_not_added() {
+  let mut host = McpHost::new();
+  host
+    .add_client("a", connect(ToolServer::new("a", &["x"])).await)
+    .await
+    .unwrap();
+
+  let error = host
+    .add_client("b", connect(ToolServer::broken("b")).await)
+    .await
+    .unwrap_err();
+  assert!(error.to_string().contains("tools unavailable"), "{}", error);
+  assert_eq!(host.server_names().collect::<Vec<_>>(), ["a"]);
+  assert_eq!(tool_names(&host), ["x"]);
+
+  // The name is free again
+  host
+    .add_client("b", connect(ToolServer::new("b", &["y"])).await)
+    .await
+    .unwrap();
+  assert_eq!(tool_names(&host), ["x", "y"]);
+  let result = host.call_tool("y", json!({})).await.unwrap();
+  assert_eq!(result["content"][0]["text"], "b/y");
+}
+
+#[tokio::test]
+async fn exposed_tool_names_are_unique() {
+  let mut host = McpHost::new();
+  host
+    .add_client(
+      "a",
+      connect(ToolServer::new("a", &["x", "b__x", "z"])).await,
+    )
+    .await
+    .unwrap();
+  host
+    .add_client("b", connect(ToolServer::new("b", &["x"])).await)
+    .await
+    .unwrap();
+
+  // `x` is shared, and once prefixed b's `b__x` clashes with a's own `b__x`
+  assert_eq!(tool_names(&host), ["a__x", "a__b__x", "z", "b__x"]);
+  for (exposed, owner) in [
+    ("a__x", "a/x"),
+    ("a__b__x", "a/b__x"),
+    ("z", "a/z"),
+    ("b__x", "b/x"),
+  ] {
+    let result = host.call_tool(exposed, json!({})).await.unwrap();
+    assert_eq!(result["content"][0]["text"], owner);
+  }
+
+  // No name left to tell two tools of the same server apart
+  let error = host
+    .add_client("c", connect(ToolServer::new("c", &["y", "y"])).await)
+    .await
+    .unwrap_err();
+  assert!(error.to_string().contains("both be exposed"), "{}", error);
+}
+
+#[tokio::test]
+async fn exposed_tool_names_are_valid_function_names() {
+  let long: &'static str = "t".repeat(70).leak();
+  let mut host = McpHost::new();
+  host
+    .add_client(
+      "weather-v2",
+      connect(ToolServer::new(
+        "weather-v2",
+        &["github.search", "天气", long],
+      ))
+      .await,
+    )
+    .await
+    .unwrap();
+  assert_eq!(
+    tool_names(&host),
+    ["github_search", "__", &long[..MAX_TOOL_NAME_LEN]]
+  );
+  // Calls still reach the tool under its real name
+  let result = host.call_tool("github_search", json!({})).await.unwrap();
+  assert_eq!(result["content"][0]["text"], "weather-v2/github.search");
+  let result = host.call_tool("__", json!({})).await.unwrap();
+  assert_eq!(result["content"][0]["text"], "weather-v2/天气");
+
+  // Both names map to `a_b`
+  let error = host
+    .add_client("c", connect(ToolServer::new("c", &["a.b", "a_b"])).await)
+    .await
+    .unwrap_err();
+  assert!(
+    error.to_string().contains("both be exposed as `c__a_b`"),
+    "{}",
+    error
+  );
+
+  for name in ["weather.v2", "天气", "", "a__b", &"s".repeat(65)] {
+    let error = host
+      .add_client(name, connect(ToolServer::new("d", &["y"])).await)
+      .await
+      .unwrap_err();
+    assert!(
+      error.to_string().contains("Invalid MCP server name"),
+      "{}",
+      error
+    );
+  }
+}
+
+#[tokio::test]
+async fn failed_refreshes_are_retried() {
+  let a = ToolServer::new("a", &["x"]);
+  let b = ToolServer::new("b", &["y"]);
+  let (client_a, peer_a) = connect_with_peer(a.clone()).await;
+  let (client_b, peer_b) = connect_with_peer(b.clone()).await;
+  let mut host = McpHost::new();
+  host.add_client("a", client_a).await.unwrap();
+  host.add_client("b", client_b).await.unwrap();
+
+  // a lists fine, b fails after both announced a change
+  a.set_tools(Some(&["x", "z"]));
+  b.set_tools(None);
+  peer_a.notify_tool_list_changed().await.unwrap();
+  peer_b.notify_tool_list_changed().await.unwrap();
+  loop {
+    match host.refresh_tools_if_changed().await {
+      Ok(false) => tokio::time::sleep(Duration::from_millis(10)).await,
+      Ok(true) => panic!("refreshed with a failing server"),
+      Err(e) => {
+        assert!(e.to_string().contains("tools unavailable"), "{}", e);
+        break;
+      }
+    }
+  }
+  assert_eq!(tool_names(&host), ["x", "y"]);
+
+  // Still pending on the next turn, even with nothing announced since
+  b.set_tools(Some(&["y"]));
+  assert!(host.refresh_tools_if_changed().await.unwrap());
+  assert_eq!(tool_names(&host), ["x", "z", "y"]);
+  assert!(!host.refresh_tools_if_changed().await.unwrap());
+}
+
+// Server with nothing but the default (empty) capabilities
+struct EmptyServer;
+
+impl ServerHandler for EmptyServer {}
+
+#[tokio::test]
+async fn servers_without_tools_are_kept() {
+  let mut host = McpHost::new();
+  host
+    .add_client("a", connect(ToolServer::new("a", &["x"])).await)
+    .await
+    .unwrap();
+  host
+    .add_client("docs", connect(EmptyServer).await)
+    .await
+    .unwrap();
+
+  assert_eq!(host.server_names().collect::<Vec<_>>(), ["a", "docs"]);
+  assert_eq!(tool_names(&host), ["x"]);
+  host.refresh_tools().await.unwrap();
+  assert_eq!(tool_names(&host), ["x"]);
+}
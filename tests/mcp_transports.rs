@@ -0,0 +1,820 @@
+// End-to-end tests of McpClient against real servers: the mcp_server binary
+// over stdio, in-process servers over stdio pipes and Streamable HTTP and,
+// for behaviour SimpleMcpServer never shows, a bare rmcp server over an
+// in-memory pipe
+
+use async_trait::async_trait;
+use deepseek_agent::mcp_client::{McpClient, Progress, ServerCommand};
+use deepseek_agent::mcp_host::McpHost;
+use deepseek_agent::mcp_protocol::{
+  INVALID_PARAMS, INVALID_REQUEST, JsonRpcError, METHOD_NOT_FOUND, PARSE_ERROR,
+};
+use deepseek_agent::mcp_registry::{
+  BoxError, ListKind, McpRegistry, ResourceTemplate, ResourceUpdates, TemplateArguments, Tool,
+  ToolContext, ToolOutput,
+};
+use deepseek_agent::mcp_schema::InvalidArguments;
+use deepseek_agent::mcp_server_http::{self, MCP_ENDPOINT};
+use deepseek_agent::mcp_server_simple::SimpleMcpServer;
+use rmcp::model::{
+  ErrorData, ListToolsResult, PaginatedRequestParams, ServerCapabilities, ServerInfo,
+};
+use rmcp::service::{RequestContext, RoleServer};
+use rmcp::{ServerHandler, ServiceExt};
+use serde_json::{Value, json};
+use std::net::TcpListener;
+use std::process::Stdio;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::{Arc, Mutex};
+use std::time::Duration;
+use tokio::io::{AsyncBufReadExt, AsyncRead, AsyncWrite, AsyncWriteExt, BufReader};
+use tokio::process::{Child, Command};
+use tokio::sync::Notify;
+
+struct EchoTool;
+
+#[async_trait]
+impl Tool for EchoTool {
+  fn name(&self) -> &str {
+    "echo"
+  }
+
+  fn description(&self) -> &str {
+    "Echo the text back in two steps"
+  }
+
+  fn input_schema(&self) -> Value {
+    json!({
+      "type": "object",
+      "properties": {
+        "text": {"type": "string"}
+      },
+      "required": ["text"]
+    })
+  }
+
+  async fn execute(&self, arguments: Value, context: ToolContext) -> Result<ToolOutput, BoxError> {
+    context.progress(1.0, Some(2.0), Some("first half"));
+    context.progress(2.0, Some(2.0), Some("second half"));
+    Ok(ToolOutput::text(
+      arguments["text"].as_str().unwrap_or_default(),
+    ))
+  }
+}
+
+struct GreetingTemplate;
+
+#[async_trait]
+impl ResourceTemplate for GreetingTemplate {
+  fn uri_template(&self) -> &str {
+    "greeting://{name}/text"
+  }
+
+  fn name(&self) -> &str {
+    "Greeting"
+  }
+
+  fn description(&self) -> &str {
+    "Greets the name in the URI"
+  }
+
+  fn mime_type(&self) -> &str {
+    "text/plain"
+  }
+
+  async fn read(&self, arguments: TemplateArguments) -> Result<String, BoxError> {
+    Ok(format!("你好, {}", arguments["name"]))
+  }
+}
+
+// Runs until cancelled; tells when it started and when its future is gone
+struct SlowTool {
+  started: Arc<Notify>,
+  dropped: Arc<AtomicBool>,
+}
+
+struct DropFlag(Arc<AtomicBool>);
+
+impl Drop for DropFlag {
+  fn drop(&mut self) {
+    self.0.store(true, Ordering::SeqCst);
+  }
+}
+
+#[async_trait]
+impl Tool for SlowTool {
+  fn name(&self) -> &str {
+    "slow"
+  }
+
+  fn description(&self) -> &str {
+    "Never finishes"
+  }
+
+  fn input_schema(&self) -> Value {
+    json!({"type": "object", "properties": {}})
+  }
+
+  async fn execute(
+    &self,
+    _arguments: Value,
+    _context: ToolContext,
+  ) -> Result<ToolOutput, BoxError> {
+    let _flag = DropFlag(self.dropped.clone());
+    self.started.notify_one();
+    std::future::pending().await
+  }
+}
+
+struct PingTool;
+
+#[async_trait]
+impl Tool for PingTool {
+  fn name(&self) -> &str {
+    "ping"
+  }
+
+  fn description(&self) -> &str {
+    "Answer pong"
+  }
+
+  fn input_schema(&self) -> Value {
+    json!({"type": "object", "properties": {}})
+  }
+
+  async fn execute(
+    &self,
+    _arguments: Value,
+    _context: ToolContext,
+  ) -> Result<ToolOutput, BoxError> {
+    Ok(ToolOutput::text("pong"))
+  }
+}
+
+// Hands out the tools/list cursors a, b, a, b, ... and never a last page
+#[derive(Clone)]
+struct CyclingServer;
+
+impl ServerHandler for CyclingServer {
+  fn get_info(&self) -> ServerInfo {
+    ServerInfo {
+      capabilities: ServerCapabilities::builder().enable_tools().build(),
+      ..Default::default()
+    }
+  }
+
+  async fn list_tools(
+    &self,
+    request: Option<PaginatedRequestParams>,
+    _context: RequestContext<RoleServer>,
+  ) -> Result<ListToolsResult, ErrorData> {
+    let next = match request.and_then(|request| request.cursor).as_deref() {
+      Some("a") => "b",
+      _ => "a",
+    };
+    Ok(ListToolsResult {
+      next_cursor: Some(next.to_string()),
+      ..Default::default()
+    })
+  }
+}
+
+fn stdio_server() -> ServerCommand {
+  // The tools only need the keys when they are called
+  ServerCommand::new(env!("CARGO_BIN_EXE_mcp_server"))
+    .env("AMAP_API_KEY", "test")
+    .env("SERPER_API_KEY", "test")
+}
+
+// A stdio server spoken to line by line, for messages McpClient never sends
+struct RawStdioServer {
+  _child: Option<Child>,
+  stdin: Box<dyn AsyncWrite + Unpin + Send>,
+  stdout: tokio::io::Lines<BufReader<Box<dyn AsyncRead + Unpin + Send>>>,
+}
+
+impl RawStdioServer {
+  fn start() -> Self {
+    let mut child = Command::new(env!("CARGO_BIN_EXE_mcp_server"))
+      .env("AMAP_API_KEY", "test")
+      .env("SERPER_API_KEY", "test")
+      .stdin(Stdio::piped())
+      .stdout(Stdio::piped())
+      .stderr(Stdio::null())
+      .kill_on_drop(true)
+      .spawn()
+      .unwrap();
+    Self {
+      stdin: Box::new(child.stdin.take().unwrap()),
+      stdout: BufReader::new(Box::new(child.stdout.take().unwrap()) as Box<_>).lines(),
+      _child: Some(child),
+    }
+  }
+
+  // `server` run in-process over an in-memory pipe
+  fn in_process(server: SimpleMcpServer) -> Self {
+    let (client_io, server_io) = tokio::io::duplex(64 * 1024);
+    tokio::spawn(async move {
+      let (reader, writer) = tokio::io::split(server_io);
+      let _ = server.run_stdio_with(reader, writer).await;
+    });
+    let (reader, writer) = tokio::io::split(client_io);
+    Self {
+      stdin: Box::new(writer),
+      stdout: BufReader::new(Box::new(reader) as Box<_>).lines(),
+      _child: None,
+    }
+  }
+
+  async fn initialize(&mut self) {
+    let response = self
+      .request(json!({
+        "jsonrpc": "2.0",
+        "id": 1,
+        "method": "initialize",
+        "params": {
+          "protocolVersion": "2025-06-18",
+          "capabilities": {},
+          "clientInfo": {"name": "raw", "version": "0"}
+        }
+      }))
+      .await;
+    assert!(response["result"].is_object(), "{}", response);
+    self
+      .send(r#"{"jsonrpc": "2.0", "method": "notifications/initialized"}"#)
+      .await;
+  }
+
+  async fn send(&mut self, line: &str) {
+    self
+      .stdin
+      .write_all(format!("{}\n", line).as_bytes())
+      .await
+      .unwrap();
+  }
+
+  async fn receive(&mut self) -> Value {
+    let line = tokio::time::timeout(Duration::from_secs(5), self.stdout.next_line())
+      .await
+      .unwrap()
+      .unwrap()
+      .unwrap();
+    serde_json::from_str(&line).unwrap()
+  }
+
+  async fn request(&mut self, message: Value) -> Value {
+    self.send(&message.to_string()).await;
+    self.receive().await
+  }
+}
+
+// Registry with the echo tool and the greeting template
+fn test_registry() -> McpRegistry {
+  McpRegistry::new()
+    .with_tool(EchoTool)
+    .with_resource_template(GreetingTemplate)
+}
+
+fn start_http_server() -> String {
+  start_http_server_with(test_registry())
+}
+
+fn start_http_server_with(registry: McpRegistry) -> String {
+  serve_http(SimpleMcpServer::with_registry(registry))
+}
+
+// Serve on a free local port
+fn serve_http(server: SimpleMcpServer) -> String {
+  let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+  let url = format!("http://{}{}", listener.local_addr().unwrap(), MCP_ENDPOINT);
+  tokio::spawn(async move {
+    let _ = mcp_server_http::serve(server, listener).await;
+  });
+  url
+}
+
+fn text_content(result: &Value) -> &str {
+  result["content"][0]["text"].as_str().unwrap_or_default()
+}
+
+#[tokio::test]
+async fn stdio_lists_and_validates_tools() {
+  let client = McpClient::new(stdio_server()).await.unwrap();
+  let server = client.server();
+  assert_eq!(server.server_info["name"], "deepseek-agent");
+  assert_eq!(server.server_info["version"], env!("CARGO_PKG_VERSION"));
+
+  let tools = client.list_tools().await.unwrap();
+  let names: Vec<_> = tools
+    .iter()
+    .filter_map(|tool| tool["name"].as_str())
+    .collect();
+  assert!(names.contains(&"get_weather"), "tools: {:?}", names);
+  assert!(!client.list_prompts().await.unwrap().is_empty());
+
+  // Rejected by the client before anything is sent
+  let error = client
+    .call_tool("get_weather", json!({}))
+    .await
+    .unwrap_err();
+  assert!(
+    error.downcast_ref::<InvalidArguments>().is_some(),
+    "{}",
+    error
+  );
+
+  // Rejected by the server
+  let error = client
+    .send_request(
+      "tools/call",
+      json!({"name": "get_weather", "arguments": {}}),
+    )
+    .await
+    .unwrap_err();
+  let error = error.downcast_ref::<JsonRpcError>().unwrap();
+  assert_eq!(error.code, INVALID_PARAMS);
+}
+
+#[tokio::test]
+async fn stdio_answers_invalid_messages_and_keeps_serving() {
+  let mut server = RawStdioServer::start();
+
+  server.send("{not json").await;
+  let response = server.receive().await;
+  assert_eq!(response["id"], Value::Null);
+  assert_eq!(response["error"]["code"], PARSE_ERROR);
+
+  // Rejected until the handshake has completed
+  let response = server
+    .request(json!({"jsonrpc": "2.0", "id": 1, "method": "tools/list"}))
+    .await;
+  assert_eq!(response["id"], 1);
+  assert_eq!(response["error"]["code"], INVALID_REQUEST);
+
+  let response = server
+    .request(json!({
+      "jsonrpc": "2.0",
+      "id": 2,
+      "method": "initialize",
+      "params": {
+        "protocolVersion": "2025-06-18",
+        "capabilities": {},
+        "clientInfo": {"name": "raw", "version": "0.1"}
+      }
+    }))
+    .await;
+  assert_eq!(response["result"]["serverInfo"]["name"], "deepseek-agent");
+  server
+    .send(&json!({"jsonrpc": "2.0", "method": "notifications/initialized"}).to_string())
+    .await;
+
+  let response = server
+    .request(json!({"jsonrpc": "2.0", "id": 3, "method": "tools/list"}))
+    .await;
+  assert!(response["result"]["tools"].is_array(), "{}", response);
+
+  let response = server
+    .request(json!({"jsonrpc": "2.0", "id": 4, "method": "tools/call", "params": {}}))
+    .await;
+  assert_eq!(response["error"]["code"], INVALID_PARAMS);
+  let response = server
+    .request(json!({"jsonrpc": "2.0", "id": 5, "method": "tools/unknown"}))
+    .await;
+  assert_eq!(response["error"]["code"], METHOD_NOT_FOUND);
+}
+
+#[tokio::test]
+async fn stdio_answers_batches_with_one_array() {
+  let mut server = RawStdioServer::start();
+  server
+    .send(
+      &json!([
+        {
+          "jsonrpc": "2.0",
+          "id": 1,
+          "method": "initialize",
+          "params": {
+            "protocolVersion": "2025-06-18",
+            "capabilities": {},
+            "clientInfo": {"name": "raw", "version": "0.1"}
+          }
+        },
+        {"jsonrpc": "2.0", "method": "notifications/initialized"}
+      ])
+      .to_string(),
+    )
+    .await;
+  let responses = server.receive().await;
+  assert_eq!(responses.as_array().unwrap().len(), 1);
+  assert_eq!(responses[0]["id"], 1);
+
+  let responses = server
+    .request(json!([
+      {"jsonrpc": "2.0", "id": 2, "method": "tools/list"},
+      {"jsonrpc": "2.0", "id": 3, "method": "ping"},
+      {"id": 4},
+      {"jsonrpc": "2.0", "method": "notifications/cancelled", "params": {"requestId": 9}}
+    ]))
+    .await;
+  let mut responses = responses.as_array().unwrap().clone();
+  responses.sort_by_key(|response| response["id"].as_i64());
+  assert_eq!(responses.len(), 3, "{:?}", responses);
+  assert_eq!(responses[0]["id"], 2);
+  assert!(responses[0]["result"]["tools"].is_array());
+  assert_eq!(responses[1]["id"], 3);
+  assert_eq!(responses[1]["result"], json!({}));
+  assert_eq!(responses[2]["id"], 4);
+  assert_eq!(responses[2]["error"]["code"], INVALID_REQUEST);
+
+  let response = server.request(json!([])).await;
+  assert_eq!(response["id"], Value::Null);
+  assert_eq!(response["error"]["code"], INVALID_REQUEST);
+}
+
+#[tokio::test]
+async fn http_initializes_a_session_and_calls_tools() {
+  let client = McpClient::connect_http(&start_http_server()).await.unwrap();
+  assert_eq!(client.server().server_info["name"], "deepseek-agent");
+
+  let tools = client.list_tools().await.unwrap();
+  assert_eq!(tools.len(), 1);
+  assert_eq!(tools[0]["name"], "echo");
+
+  let result = client
+    .call_tool("echo", json!({"text": "hello"}))
+    .await
+    .unwrap();
+  assert_eq!(text_content(&result), "hello");
+
+  let error = client
+    .send_request(
+      "tools/call",
+      json!({"name": "echo", "arguments": {"text": 1}}),
+    )
+    .await
+    .unwrap_err();
+  let error = error.downcast_ref::<JsonRpcError>().unwrap();
+  assert_eq!(error.code, INVALID_PARAMS);
+
+  // Params that don't fit the method at all
+  let error = client
+    .send_request("tools/call", json!({}))
+    .await
+    .unwrap_err();
+  let error = error.downcast_ref::<JsonRpcError>().unwrap();
+  assert_eq!(error.code, INVALID_PARAMS);
+}
+
+#[tokio::test]
+async fn http_streams_progress_before_the_result() {
+  let client = McpClient::connect_http(&start_http_server()).await.unwrap();
+  client.list_tools().await.unwrap();
+
+  let updates = Arc::new(Mutex::new(Vec::new()));
+  let received = updates.clone();
+  let result = client
+    .call_tool_with_progress("echo", json!({"text": "hi"}), move |progress: Progress| {
+      received.lock().unwrap().push(progress);
+    })
+    .await
+    .unwrap();
+  assert_eq!(text_content(&result), "hi");
+
+  let updates = updates.lock().unwrap();
+  let steps: Vec<_> = updates.iter().map(|update| update.progress).collect();
+  assert_eq!(steps, [1.0, 2.0]);
+  assert_eq!(updates[1].message.as_deref(), Some("second half"));
+}
+
+#[tokio::test]
+async fn http_reads_resources_through_templates() {
+  let client = McpClient::connect_http(&start_http_server()).await.unwrap();
+
+  let templates = client.list_resource_templates().await.unwrap();
+  assert_eq!(templates.len(), 1);
+  assert_eq!(templates[0]["uriTemplate"], "greeting://{name}/text");
+
+  let result = client
+    .read_resource("greeting://%E4%B8%96%E7%95%8C/text")
+    .await
+    .unwrap();
+  assert_eq!(result["contents"][0]["text"], "你好, 世界");
+  assert_eq!(result["contents"][0]["mimeType"], "text/plain");
+
+  // Variables never span path segments
+  let error = client
+    .read_resource("greeting://a/b/text")
+    .await
+    .unwrap_err();
+  let error = error.downcast_ref::<JsonRpcError>().unwrap();
+  assert_eq!(error.code, INVALID_PARAMS);
+}
+
+#[tokio::test]
+async fn http_notifies_subscribers_of_resource_updates() {
+  let updates = ResourceUpdates::new();
+  let registry = test_registry().with_resource_updates(updates.clone());
+  let client = McpClient::connect_http(&start_http_server_with(registry))
+    .await
+    .unwrap();
+  let mut stream = client.resource_updates();
+  let (handled, mut handled_uris) = tokio::sync::mpsc::unbounded_channel();
+  client.on_notification("notifications/resources/updated", move |params| {
+    let _ = handled.send(params["uri"].as_str().unwrap_or_default().to_string());
+  });
+  let uri = "greeting://%E4%B8%96%E7%95%8C/text";
+  client.subscribe_resource(uri).await.unwrap();
+
+  // Published until one gets through, as the client's event stream opens
+  // in the background
+  let received = tokio::time::timeout(Duration::from_secs(5), async {
+    loop {
+      updates.publish("greeting://someone/text");
+      updates.publish("greeting://世界/text");
+      if let Ok(uri) = tokio::time::timeout(Duration::from_millis(50), stream.recv()).await {
+        break uri;
+      }
+    }
+  })
+  .await
+  .unwrap();
+  // Reported with the URI as subscribed, other resources are left out
+  assert_eq!(received.as_deref(), Some(uri));
+  // The notification handler sees it as well
+  let handled = tokio::time::timeout(Duration::from_secs(1), handled_uris.recv())
+    .await
+    .unwrap();
+  assert_eq!(handled.as_deref(), Some(uri));
+
+  client.unsubscribe_resource(uri).await.unwrap();
+  while tokio::time::timeout(Duration::from_millis(100), stream.recv())
+    .await
+    .is_ok()
+  {}
+  updates.publish("greeting://世界/text");
+  assert!(
+    tokio::time::timeout(Duration::from_millis(200), stream.recv())
+      .await
+      .is_err()
+  );
+
+  let error = client
+    .subscribe_resource("missing://resource")
+    .await
+    .unwrap_err();
+  let error = error.downcast_ref::<JsonRpcError>().unwrap();
+  assert_eq!(error.code, INVALID_PARAMS);
+}
+
+#[tokio::test]
+async fn resource_templates_can_be_registered_at_runtime() {
+  let registry = test_registry();
+  let mut changes = registry.list_changes();
+  let client = McpClient::connect_http(&start_http_server_with(registry.clone()))
+    .await
+    .unwrap();
+  let uri = "greeting://%E4%B8%96%E7%95%8C/text";
+
+  assert!(registry.unregister_resource_template("greeting://{name}/text"));
+  assert!(!registry.unregister_resource_template("greeting://{name}/text"));
+  assert_eq!(changes.try_recv(), Ok(ListKind::Resources));
+  assert!(changes.try_recv().is_err());
+  assert!(client.list_resource_templates().await.unwrap().is_empty());
+  assert!(client.read_resource(uri).await.is_err());
+
+  // Registering twice replaces the template instead of adding another
+  registry.register_resource_template(GreetingTemplate);
+  registry.register_resource_template(GreetingTemplate);
+  assert_eq!(changes.try_recv(), Ok(ListKind::Resources));
+  assert_eq!(changes.try_recv(), Ok(ListKind::Resources));
+  assert_eq!(client.list_resource_templates().await.unwrap().len(), 1);
+  let result = client.read_resource(uri).await.unwrap();
+  assert_eq!(result["contents"][0]["text"], "你好, 世界");
+}
+
+#[tokio::test]
+async fn http_tool_list_changes_reach_the_host() {
+  let registry = test_registry();
+  let client = McpClient::connect_http(&start_http_server_with(registry.clone()))
+    .await
+    .unwrap();
+  assert_eq!(client.server().capabilities["tools"]["listChanged"], true);
+  let mut host = McpHost::new();
+  host.add_client("local", client).await.unwrap();
+
+  let tool_names = |host: &McpHost| -> Vec<String> {
+    host
+      .list_tools()
+      .iter()
+      .filter_map(|tool| tool["name"].as_str().map(str::to_string))
+      .collect()
+  };
+  assert_eq!(tool_names(&host), ["echo"]);
+
+  // Registered again until announced, as the client's event stream opens
+  // in the background
+  tokio::time::timeout(Duration::from_secs(5), async {
+    loop {
+      registry.register_tool(PingTool);
+      tokio::time::sleep(Duration::from_millis(50)).await;
+      if host.refresh_tools_if_changed().await.unwrap() {
+        break;
+      }
+    }
+  })
+  .await
+  .unwrap();
+  assert_eq!(tool_names(&host), ["echo", "ping"]);
+
+  assert!(registry.unregister_tool("echo"));
+  tokio::time::timeout(Duration::from_secs(5), async {
+    while !host.refresh_tools_if_changed().await.unwrap() {
+      tokio::time::sleep(Duration::from_millis(20)).await;
+    }
+  })
+  .await
+  .unwrap();
+  assert_eq!(tool_names(&host), ["ping"]);
+  assert!(!host.refresh_tools_if_changed().await.unwrap());
+}
+
+#[tokio::test]
+async fn http_sessions_end_with_their_client() {
+  let server = SimpleMcpServer::with_registry(test_registry());
+  let client = McpClient::connect_http(&serve_http(server.clone()))
+    .await
+    .unwrap();
+  client.list_tools().await.unwrap();
+  assert_eq!(server.session_count(), 1);
+
+  // Dropping the client ends its session on the server
+  drop(client);
+  tokio::time::timeout(Duration::from_secs(5), async {
+    while server.session_count() > 0 {
+      tokio::time::sleep(Duration::from_millis(20)).await;
+    }
+  })
+  .await
+  .unwrap();
+}
+
+#[tokio::test]
+async fn http_lists_are_paginated() {
+  let registry = test_registry().with_tool(PingTool);
+  let server = SimpleMcpServer::with_registry(registry).with_page_size(1);
+  let client = McpClient::connect_http(&serve_http(server)).await.unwrap();
+
+  let page = client.send_request("tools/list", json!({})).await.unwrap();
+  assert_eq!(page["tools"].as_array().unwrap().len(), 1);
+  let cursor = page["nextCursor"].as_str().unwrap();
+  let page = client
+    .send_request("tools/list", json!({"cursor": cursor}))
+    .await
+    .unwrap();
+  assert_eq!(page["tools"][0]["name"], "ping");
+  assert!(page.get("nextCursor").is_none());
+
+  // The client follows the cursors on its own
+  let tools = client.list_tools().await.unwrap();
+  let names: Vec<_> = tools
+    .iter()
+    .filter_map(|tool| tool["name"].as_str())
+    .collect();
+  assert_eq!(names, ["echo", "ping"]);
+
+  let error = client
+    .send_request("tools/list", json!({"cursor": "not-a-cursor"}))
+    .await
+    .unwrap_err();
+  let error = error.downcast_ref::<JsonRpcError>().unwrap();
+  assert_eq!(error.code, INVALID_PARAMS);
+}
+
+#[tokio::test]
+async fn cycling_cursors_are_rejected() {
+  let (client_io, server_io) = tokio::io::duplex(4096);
+  tokio::spawn(async move {
+    if let Ok(service) = CyclingServer.serve(server_io).await {
+      let _ = service.waiting().await;
+    }
+  });
+  let client = McpClient::with_transport(client_io).await.unwrap();
+
+  let error = tokio::time::timeout(Duration::from_secs(5), client.list_tools())
+    .await
+    .unwrap()
+    .unwrap_err();
+  assert!(
+    error.to_string().contains("repeated cursor `a`"),
+    "{}",
+    error
+  );
+}
+
+#[tokio::test]
+async fn http_requests_without_a_session_are_rejected() {
+  let url = start_http_server();
+  let response = reqwest::Client::new()
+    .post(&url)
+    .header("accept", "application/json, text/event-stream")
+    .json(&json!({"jsonrpc": "2.0", "id": 1, "method": "tools/list"}))
+    .send()
+    .await
+    .unwrap();
+  assert_eq!(response.status(), reqwest::StatusCode::BAD_REQUEST);
+
+  let response = reqwest::Client::new()
+    .post(&url)
+    .header("accept", "application/json, text/event-stream")
+    .header("mcp-session-id", "expired")
+    .json(&json!({"jsonrpc": "2.0", "id": 1, "method": "tools/list"}))
+    .send()
+    .await
+    .unwrap();
+  assert_eq!(response.status(), reqwest::StatusCode::NOT_FOUND);
+}
+
+fn slow_server() -> (SimpleMcpServer, Arc<Notify>, Arc<AtomicBool>) {
+  let started = Arc::new(Notify::new());
+  let dropped = Arc::new(AtomicBool::new(false));
+  let registry = McpRegistry::new().with_tool(SlowTool {
+    started: started.clone(),
+    dropped: dropped.clone(),
+  });
+  (SimpleMcpServer::with_registry(registry), started, dropped)
+}
+
+async fn wait_until_dropped(dropped: &AtomicBool) {
+  tokio::time::timeout(Duration::from_secs(5), async {
+    while !dropped.load(Ordering::SeqCst) {
+      tokio::time::sleep(Duration::from_millis(10)).await;
+    }
+  })
+  .await
+  .unwrap();
+}
+
+#[tokio::test]
+async fn stdio_timed_out_calls_stop_the_tool() {
+  let (server, started, dropped) = slow_server();
+  let (client_io, server_io) = tokio::io::duplex(64 * 1024);
+  tokio::spawn(async move {
+    let (reader, writer) = tokio::io::split(server_io);
+    let _ = server.run_stdio_with(reader, writer).await;
+  });
+  let client = McpClient::with_transport(client_io).await.unwrap();
+  client.list_tools().await.unwrap();
+
+  let call = client.call_tool("slow", json!({}));
+  assert!(
+    tokio::time::timeout(Duration::from_millis(200), call)
+      .await
+      .is_err()
+  );
+  started.notified().await;
+  // Dropping the call sent notifications/cancelled, which drops the tool
+  wait_until_dropped(&dropped).await;
+  assert_eq!(client.list_tools().await.unwrap().len(), 1);
+}
+
+#[tokio::test]
+async fn stdio_drops_responses_to_cancelled_requests() {
+  let (server, started, dropped) = slow_server();
+  let mut server = RawStdioServer::in_process(server);
+  server.initialize().await;
+
+  let call = json!({
+    "jsonrpc": "2.0", "id": 2, "method": "tools/call",
+    "params": {"name": "slow", "arguments": {}}
+  });
+  server.send(&call.to_string()).await;
+  started.notified().await;
+  server
+    .send(r#"{"jsonrpc": "2.0", "method": "notifications/cancelled", "params": {"requestId": 2}}"#)
+    .await;
+  wait_until_dropped(&dropped).await;
+  // Only the pings are answered
+  for id in [3, 4] {
+    let response = server
+      .request(json!({"jsonrpc": "2.0", "id": id, "method": "ping"}))
+      .await;
+    assert_eq!(response["id"], id, "{}", response);
+  }
+
+  // A batch stops waiting for a cancelled request
+  let batch = json!([
+    {
+      "jsonrpc": "2.0", "id": 5, "method": "tools/call",
+      "params": {"name": "slow", "arguments": {}}
+    },
+    {"jsonrpc": "2.0", "id": 6, "method": "ping"}
+  ]);
+  server.send(&batch.to_string()).await;
+  started.notified().await;
+  server
+    .send(r#"{"jsonrpc": "2.0", "method": "notifications/cancelled", "params": {"requestId": 5}}"#)
+    .await;
+  let responses = server.receive().await;
+  assert_eq!(responses.as_array().map(Vec::len), Some(1), "{}", responses);
+  assert_eq!(responses[0]["id"], 6);
+}
@@ -0,0 +1,50 @@
+// The query history behind the recent-queries resources
+
+use deepseek_agent::mcp_registry::{Resource, ToolOutput};
+use deepseek_agent::tools::history::{QueryHistory, QueryStatus};
+use deepseek_agent::tools::resources::RecentWeatherQueries;
+use serde_json::Value;
+use std::fs;
+
+#[test]
+fn history_is_bounded_and_survives_a_reopen() {
+  let dir = std::env::temp_dir().join(format!("query-history-{}", std::process::id()));
+  let path = dir.join("weather-queries.json");
+  let _ = fs::remove_dir_all(&dir);
+
+  let history = QueryHistory::open(&path, 2);
+  history.record_output("上海", &Ok(ToolOutput::text("晴")));
+  history.record_output("北京", &Ok(ToolOutput::error("Weather API error")));
+  history.record_output("深圳", &Err("timeout".into()));
+
+  let queries = |history: &QueryHistory| -> Vec<String> {
+    history
+      .recent()
+      .into_iter()
+      .map(|record| record.query)
+      .collect()
+  };
+  assert_eq!(queries(&history), ["深圳", "北京"]);
+  let recent = history.recent();
+  assert_eq!(recent[0].status, QueryStatus::Error);
+  assert_eq!(recent[0].error.as_deref(), Some("timeout"));
+
+  // Dropping the last clone waits for the file to be written
+  drop(history);
+  let reopened = QueryHistory::open(&path, 2);
+  assert_eq!(reopened.recent(), recent);
+
+  fs::remove_dir_all(&dir).unwrap();
+}
+
+#[tokio::test]
+async fn recent_queries_resource_reads_the_history() {
+  let history = QueryHistory::new(10);
+  let resource = RecentWeatherQueries::new(history.clone());
+  history.record("上海", QueryStatus::Ok, None);
+
+  let contents: Value = serde_json::from_str(&resource.read().await.unwrap()).unwrap();
+  assert_eq!(contents["recent_queries"][0]["query"], "上海");
+  assert_eq!(contents["recent_queries"][0]["status"], "ok");
+  assert!(contents["recent_queries"][0]["timestamp"].as_u64().unwrap() > 0);
+}